@@ -0,0 +1,101 @@
+//! Measures how fast frames are emulated with a busy screen.
+//!
+//! A ROM is built on the fly: it fills VRAM and OAM with pseudo random data,
+//! so every line has background tiles and sprites to fetch, then loops forever.
+//! Run it with `cargo bench -p rustyboy-core --bench frame`.
+
+use rustyboy_core::{Cartridge, Hardware};
+use std::time::Instant;
+
+const WARMUP_FRAMES: u32 = 60;
+const FRAMES: u32 = 1200;
+const FRAMES_PER_SECOND: f64 = 59.73;
+
+const VRAM_DATA_ADDRESS: usize = 0x4000;
+const OAM_DATA_ADDRESS: usize = 0x6000;
+
+const PROGRAM: &[u8] = &[
+    0xf3, //             DI
+    0xaf, //             XOR A
+    0xe0, 0x40, //       LDH ($40),A      ; LCD off
+    0x21, 0x00, 0x80, // LD HL,$8000
+    0x11, 0x00, 0x40, // LD DE,$4000
+    0x1a, //             LD A,(DE)        ; copy $2000 bytes to VRAM
+    0x22, //             LD (HL+),A
+    0x13, //             INC DE
+    0x7c, //             LD A,H
+    0xfe, 0xa0, //       CP $A0
+    0x20, 0xf8, //       JR NZ,-8
+    0x21, 0x00, 0xfe, // LD HL,$FE00
+    0x1a, //             LD A,(DE)        ; copy $A0 bytes to OAM
+    0x22, //             LD (HL+),A
+    0x13, //             INC DE
+    0x7d, //             LD A,L
+    0xfe, 0xa0, //       CP $A0
+    0x20, 0xf8, //       JR NZ,-8
+    0x3e, 0xe4, //       LD A,$E4         ; palettes
+    0xe0, 0x47, //       LDH ($47),A
+    0xe0, 0x48, //       LDH ($48),A
+    0xe0, 0x49, //       LDH ($49),A
+    0x3e, 0x93, //       LD A,$93         ; LCD, background and sprites on
+    0xe0, 0x40, //       LDH ($40),A
+    0x18, 0xfe, //       JR -2
+];
+
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as u8
+    }
+}
+
+fn build_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+
+    // Entry point: NOP, JP $0150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xc3, 0x50, 0x01]);
+    rom[0x134..0x139].copy_from_slice(b"BENCH");
+    rom[0x150..0x150 + PROGRAM.len()].copy_from_slice(PROGRAM);
+
+    let mut random = XorShift(0x1234_5678);
+    for byte in &mut rom[VRAM_DATA_ADDRESS..OAM_DATA_ADDRESS] {
+        *byte = random.next();
+    }
+    for sprite in rom[OAM_DATA_ADDRESS..OAM_DATA_ADDRESS + 0xa0].chunks_mut(4) {
+        sprite[0] = 16 + random.next() % 144;
+        sprite[1] = 8 + random.next() % 160;
+        sprite[2] = random.next();
+        sprite[3] = random.next() & 0xf0;
+    }
+
+    rom
+}
+
+fn main() {
+    let cartridge = Cartridge::from_bytes(build_rom()).expect("Invalid benchmark ROM");
+    let mut hardware = Hardware::new(cartridge);
+
+    for _ in 0..WARMUP_FRAMES {
+        hardware.run();
+    }
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        hardware.run();
+    }
+    let elapsed = start.elapsed();
+
+    let frames_per_second = FRAMES as f64 / elapsed.as_secs_f64();
+    println!(
+        "{} frames in {:.2?}: {:.1} µs/frame, {:.0} fps ({:.1}x real time)",
+        FRAMES,
+        elapsed,
+        elapsed.as_micros() as f64 / FRAMES as f64,
+        frames_per_second,
+        frames_per_second / FRAMES_PER_SECOND
+    );
+}
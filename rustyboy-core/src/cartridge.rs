@@ -5,11 +5,14 @@ use std::{
     path::PathBuf,
 };
 
+const CGB_FLAG_ADDRESS: usize = 0x143;
+const SGB_FLAG_ADDRESS: usize = 0x146;
 const MBC_TYPE_ADDRESS: usize = 0x147;
 
 #[derive(Clone, Debug)]
 pub struct Mbc1State {
     selected_rom_bank: u8,
+    ram: Vec<u8>,
 }
 
 #[derive(Clone, Debug)]
@@ -24,6 +27,7 @@ pub struct NoMbcState {
     ram: Vec<u8>,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, Debug)]
 pub enum Mbc {
     NoMbc(NoMbcState),
@@ -34,14 +38,18 @@ pub enum Mbc {
 #[derive(Clone, Debug)]
 pub struct CartridgeHeader {
     pub title: String,
+    /// The cartridge runs in CGB mode on a CGB, either as CGB only or CGB compatible
+    pub cgb_support: bool,
+    /// The cartridge uses the SGB functions, which also requires the new licensee code
+    pub sgb_support: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct Cartridge {
-    path: String,
+    path: Option<String>,
     pub header: CartridgeHeader,
-    pub data: Vec<u8>,
-    pub mbc: Mbc,
+    pub(crate) data: Vec<u8>,
+    pub(crate) mbc: Mbc,
 }
 
 impl Cartridge {
@@ -50,17 +58,30 @@ impl Cartridge {
         let mut cartridge_data = vec![];
         cartridge_file.read_to_end(&mut cartridge_data)?;
 
-        Cartridge::from_data(path, cartridge_data)
+        Cartridge::from_data(Some(path), cartridge_data)
+    }
+
+    /// Creates a cartridge from a ROM image kept in memory.
+    ///
+    /// Battery backed RAM is not persisted since there is no file to save it next to.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Cartridge::from_data(None, data)
     }
 
-    fn from_data(path: String, data: Vec<u8>) -> Result<Self> {
-        if data.len() < 0x8000 || data.len() % 0x4000 != 0 {
+    fn from_data(path: Option<String>, data: Vec<u8>) -> Result<Self> {
+        if data.len() < 0x8000 || !data.len().is_multiple_of(0x4000) {
             return Err(anyhow::anyhow!("Invalid cartridge size"));
         }
 
         let title = Cartridge::parse_title(&data)?;
-        let header = CartridgeHeader { title };
-        let mbc = Cartridge::parse_mbc(&path, &data)?;
+        let cgb_support = data[CGB_FLAG_ADDRESS] & 0x80 != 0;
+        let sgb_support = data[SGB_FLAG_ADDRESS] == 0x03 && Cartridge::is_new_cartridge(&data);
+        let header = CartridgeHeader {
+            title,
+            cgb_support,
+            sgb_support,
+        };
+        let mbc = Cartridge::parse_mbc(path.as_deref(), &data)?;
 
         Ok(Cartridge {
             path,
@@ -86,14 +107,15 @@ impl Cartridge {
         Ok(title.trim_end_matches('\0').to_string())
     }
 
-    fn parse_mbc(path: &str, data: &[u8]) -> Result<Mbc> {
+    fn parse_mbc(path: Option<&str>, data: &[u8]) -> Result<Mbc> {
         let mbc_type = data[MBC_TYPE_ADDRESS];
         let mbc_state = match mbc_type {
             0x00 => Mbc::NoMbc(NoMbcState {
                 ram: vec![0; 0x2000],
             }),
-            0x01 => Mbc::Mbc1(Mbc1State {
+            0x01..=0x03 => Mbc::Mbc1(Mbc1State {
                 selected_rom_bank: 1,
+                ram: vec![0; 0x2000],
             }),
             0x13 => Mbc::Mbc3(Mbc3State {
                 selected_rom_bank: 1,
@@ -105,8 +127,8 @@ impl Cartridge {
         Ok(mbc_state)
     }
 
-    fn get_save_file_path(path: &str) -> Option<String> {
-        let mut save_path = PathBuf::from(path);
+    fn get_save_file_path(path: Option<&str>) -> Option<String> {
+        let mut save_path = PathBuf::from(path?);
         save_path.set_extension("sav");
 
         let path = save_path.to_str()?;
@@ -114,7 +136,7 @@ impl Cartridge {
         Some(String::from(path))
     }
 
-    fn load_mbc_ram(path: &str, length: usize) -> Result<Vec<u8>> {
+    fn load_mbc_ram(path: Option<&str>, length: usize) -> Result<Vec<u8>> {
         if let Some(path) = Cartridge::get_save_file_path(path) {
             if let Ok(mut save_file) = File::open(path) {
                 let mut save_data = vec![];
@@ -144,6 +166,7 @@ impl Cartridge {
             },
             Mbc::Mbc1(state) => match address {
                 0x0000..=0x3fff => self.data[address as usize],
+                0xa000..=0xbfff => state.ram[address as usize - 0xa000],
                 _ => {
                     self.data[address as usize + ((state.selected_rom_bank as usize) - 1) * 0x4000]
                 }
@@ -164,17 +187,19 @@ impl Cartridge {
     #[inline(always)]
     pub fn write(&mut self, address: u16, value: u8) {
         match &mut self.mbc {
-            Mbc::NoMbc(state) => match address {
-                0xa000..=0xbfff => {
+            Mbc::NoMbc(state) => {
+                if let 0xa000..=0xbfff = address {
                     state.ram[address as usize - 0xa000] = value;
                 }
-                _ => (),
-            },
+            }
             Mbc::Mbc1(state) => match address {
                 0x2000..=0x3fff => {
                     let value = if value == 0 { 1 } else { value & 0x1f };
                     state.selected_rom_bank = value;
                 }
+                0xa000..=0xbfff => {
+                    state.ram[address as usize - 0xa000] = value;
+                }
                 _ => (),
             },
             Mbc::Mbc3(state) => match address {
@@ -189,7 +214,7 @@ impl Cartridge {
                 0x6000..=0x7fff => {
                     if value != 0 {
                         let mbc_ram = state.ram.clone();
-                        if let Err(_) = self.save_ram(&mbc_ram) {
+                        if self.save_ram(&mbc_ram).is_err() {
                             println!("Failed to save RAM");
                         }
                     }
@@ -206,8 +231,18 @@ impl Cartridge {
         }
     }
 
+    /// ROM bank mapped at `address`, 0 for addresses outside the ROM area.
+    pub fn rom_bank(&self, address: u16) -> u16 {
+        match (&self.mbc, address) {
+            (Mbc::Mbc1(state), 0x4000..=0x7fff) => state.selected_rom_bank as u16,
+            (Mbc::Mbc3(state), 0x4000..=0x7fff) => state.selected_rom_bank as u16,
+            (Mbc::NoMbc(_), 0x4000..=0x7fff) => 1,
+            _ => 0,
+        }
+    }
+
     fn save_ram(&self, ram: &[u8]) -> Result<()> {
-        if let Some(path) = Cartridge::get_save_file_path(&self.path) {
+        if let Some(path) = Cartridge::get_save_file_path(self.path.as_deref()) {
             let mut file = File::create(path)?;
             file.write_all(ram)?;
 
@@ -231,3 +266,26 @@ impl std::fmt::Display for Mbc {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mbc1_cartridge() -> Cartridge {
+        let mut data = vec![0; 0x8000];
+        // MBC1+RAM+BATTERY
+        data[MBC_TYPE_ADDRESS] = 0x03;
+        Cartridge::from_bytes(data).unwrap()
+    }
+
+    #[test]
+    fn mbc1_external_ram_reads_back_writes() {
+        let mut cartridge = mbc1_cartridge();
+        cartridge.write(0xa000, 0x12);
+        cartridge.write(0xbfff, 0x34);
+
+        assert_eq!(cartridge.read(0xa000), 0x12);
+        assert_eq!(cartridge.read(0xa001), 0x00);
+        assert_eq!(cartridge.read(0xbfff), 0x34);
+    }
+}
@@ -1,16 +1,20 @@
 mod instructions;
 pub mod interrupts;
+pub mod profiler;
 
 use std::fmt::{Display, Formatter};
 
 use self::instructions::{BitOpTarget, ByteArithmeticTarget, LoadTarget16, PushPopTarget};
+use crate::joypad::JOYPAD_STATE_ADDRESS;
 use crate::memory::Memory;
+use crate::model::Model;
 use crate::utils::int::test_add_carry_bit;
 use instructions::{
     ArithmeticTarget, ArithmeticTarget16, Instruction, JumpCondition, LoadTarget,
     MemoryAddressRegistry,
 };
 use interrupts::{Interrupt, Interrupts};
+use profiler::{FunctionAddress, Profiler, StackEvent};
 
 #[derive(Debug)]
 enum ExecutionState {
@@ -73,6 +77,38 @@ impl Registers {
         }
     }
 
+    /// Values left by the SGB boot ROM, C=0x14 tells games they run on a SGB.
+    fn new_sgb() -> Self {
+        Registers {
+            a: 0x01,
+            b: 0x00,
+            c: 0x14,
+            d: 0x00,
+            e: 0x00,
+            f: FlagsRegister::from(0x00),
+            h: 0xc0,
+            l: 0x60,
+            program_counter: 0x100,
+            stack_pointer: 0xfffe,
+        }
+    }
+
+    /// Values left by the CGB boot ROM, A=0x11 tells games they run on a CGB.
+    fn new_cgb() -> Self {
+        Registers {
+            a: 0x11,
+            b: 0x00,
+            c: 0x00,
+            d: 0xff,
+            e: 0x56,
+            f: FlagsRegister::from(0x80),
+            h: 0x00,
+            l: 0x0d,
+            program_counter: 0x100,
+            stack_pointer: 0xfffe,
+        }
+    }
+
     // 16-bit getter
     pub fn get_af(&self) -> u16 {
         ((self.a as u16) << 8) | (u8::from(&self.f) as u16)
@@ -164,18 +200,38 @@ pub struct Cpu {
     pub registers: Registers,
     pub ime: bool,
     pub halted: bool,
+    /// Stopped by STOP until a joypad input
+    pub stopped: bool,
+    pub profiler: Option<Profiler>,
 }
 
 impl Cpu {
-    pub fn new() -> Self {
+    pub fn new(model: Model) -> Self {
         Cpu {
-            registers: Registers::new(),
+            registers: match model {
+                Model::Dmg => Registers::new(),
+                Model::Sgb => Registers::new_sgb(),
+                Model::Cgb => Registers::new_cgb(),
+            },
             ime: false,
             halted: false,
+            stopped: false,
+            profiler: None,
         }
     }
 
     pub fn step(&mut self, memory_bus: &mut Memory) -> (u8, bool) {
+        if self.stopped {
+            // STOP ends when a selected joypad line goes low
+            if memory_bus.read(JOYPAD_STATE_ADDRESS) & 0x0f == 0x0f {
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.add_cycles(1);
+                }
+                return (1, true);
+            }
+            self.stopped = false;
+        }
+
         let mut interrupts = Interrupts::get_interrupts(memory_bus);
 
         if let Some(ExecutionStep {
@@ -185,14 +241,22 @@ impl Cpu {
         }) = execute_interrupts(self, memory_bus, &mut interrupts)
         {
             self.registers.program_counter = program_counter;
+            if let Some(profiler) = &mut self.profiler {
+                let function = FunctionAddress::resolve(memory_bus, program_counter);
+                profiler.enter(function, self.registers.stack_pointer);
+                profiler.add_cycles(cycles);
+            }
             return (cycles, false);
         }
 
         if self.halted {
+            if let Some(profiler) = &mut self.profiler {
+                profiler.add_cycles(1);
+            }
             return (1, true);
         }
 
-        let opcode = memory_bus.read(self.registers.program_counter);
+        let opcode = memory_bus.cpu_read(self.registers.program_counter);
         let instruction = Instruction::from_byte(opcode);
 
         let ExecutionStep {
@@ -207,18 +271,18 @@ impl Cpu {
 
         self.registers.program_counter = program_counter;
 
-        (
-            cycles,
-            if let ExecutionState::Halted = state {
-                true
-            } else {
-                false
-            },
-        )
+        if let Some(profiler) = &mut self.profiler {
+            profiler.add_cycles(cycles);
+        }
+
+        (cycles, matches!(state, ExecutionState::Halted))
     }
 
     fn execute(&mut self, memory_bus: &mut Memory, instruction: Instruction) -> ExecutionStep {
-        match instruction {
+        let stack_event = StackEvent::from_instruction(&instruction);
+        let stack_pointer = self.registers.stack_pointer;
+
+        let step = match instruction {
             Instruction::Noop => {
                 ExecutionStep::new(self.registers.program_counter.wrapping_add(1), 1)
             }
@@ -270,7 +334,7 @@ impl Cpu {
             Instruction::SetCarryFlag => execute_set_carry_flag(self),
             Instruction::Complement => execute_complement(self),
             Instruction::ComplementCarryFlag => execute_complement_carry_flag(self),
-            Instruction::Stop => execute_stop(self),
+            Instruction::Stop => execute_stop(self, memory_bus),
             Instruction::DisableInterrupts => execute_disable_interrupts(self),
             Instruction::EnableInterrupts => execute_enable_interrupts(self),
             Instruction::Halt => execute_halt(self),
@@ -323,16 +387,37 @@ impl Cpu {
             Instruction::SetBit(bit_target, target) => {
                 execute_set_bit(self, memory_bus, bit_target, target)
             }
+        };
+
+        if let (Some(profiler), Some(stack_event)) = (&mut self.profiler, stack_event) {
+            // Conditional calls and returns only touch the stack when taken
+            match stack_event {
+                StackEvent::Call if self.registers.stack_pointer != stack_pointer => {
+                    let function = FunctionAddress::resolve(memory_bus, step.program_counter);
+                    profiler.enter(function, self.registers.stack_pointer);
+                }
+                StackEvent::Return if self.registers.stack_pointer != stack_pointer => {
+                    profiler.leave(stack_pointer);
+                }
+                _ => (),
+            }
         }
+
+        step
     }
 
+    /// Takes an internal M-cycle then writes the high byte first.
     fn push(&mut self, memory_bus: &mut Memory, value: u16) {
-        self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(2);
-        memory_bus.write16(self.registers.stack_pointer, value)
+        let [low, high] = value.to_le_bytes();
+        memory_bus.cpu_tick();
+        self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(1);
+        memory_bus.cpu_write(self.registers.stack_pointer, high);
+        self.registers.stack_pointer = self.registers.stack_pointer.wrapping_sub(1);
+        memory_bus.cpu_write(self.registers.stack_pointer, low);
     }
 
     fn pop(&mut self, memory_bus: &mut Memory) -> u16 {
-        let value = memory_bus.read16(self.registers.stack_pointer);
+        let value = memory_bus.cpu_read16(self.registers.stack_pointer);
         self.registers.stack_pointer = self.registers.stack_pointer.wrapping_add(2);
         value
     }
@@ -371,9 +456,6 @@ fn execute_arithmetic(
     function: fn(cpu: &mut Cpu, target: &ArithmeticTarget, value: u8) -> ExecutionStep,
 ) -> ExecutionStep {
     let register_a = cpu.registers.a;
-    let register_hl = cpu.registers.get_hl();
-
-    let hl_value = memory_bus.read(register_hl);
 
     match target {
         ArithmeticTarget::A => function(cpu, target, register_a),
@@ -383,9 +465,12 @@ fn execute_arithmetic(
         ArithmeticTarget::E => function(cpu, target, cpu.registers.e),
         ArithmeticTarget::H => function(cpu, target, cpu.registers.h),
         ArithmeticTarget::L => function(cpu, target, cpu.registers.l),
-        ArithmeticTarget::HL => function(cpu, target, hl_value),
+        ArithmeticTarget::HL => {
+            let hl_value = memory_bus.cpu_read(cpu.registers.get_hl());
+            function(cpu, target, hl_value)
+        }
         ArithmeticTarget::Immediate => {
-            let immediate = memory_bus.read(cpu.registers.program_counter + 1);
+            let immediate = memory_bus.cpu_read(cpu.registers.program_counter + 1);
             function(cpu, target, immediate)
         }
     }
@@ -393,7 +478,7 @@ fn execute_arithmetic(
 
 fn read_byte_arithmetic_target(
     cpu: &Cpu,
-    memory_bus: &Memory,
+    memory_bus: &mut Memory,
     target: &ByteArithmeticTarget,
 ) -> u8 {
     match target {
@@ -404,7 +489,7 @@ fn read_byte_arithmetic_target(
         ByteArithmeticTarget::E => cpu.registers.e,
         ByteArithmeticTarget::H => cpu.registers.h,
         ByteArithmeticTarget::L => cpu.registers.l,
-        ByteArithmeticTarget::HL => memory_bus.read(cpu.registers.get_hl()),
+        ByteArithmeticTarget::HL => memory_bus.cpu_read(cpu.registers.get_hl()),
     }
 }
 
@@ -422,7 +507,7 @@ fn write_byte_arithmetic_target(
         ByteArithmeticTarget::E => cpu.registers.e = value,
         ByteArithmeticTarget::H => cpu.registers.h = value,
         ByteArithmeticTarget::L => cpu.registers.l = value,
-        ByteArithmeticTarget::HL => memory_bus.write(cpu.registers.get_hl(), value),
+        ByteArithmeticTarget::HL => memory_bus.cpu_write(cpu.registers.get_hl(), value),
     }
 }
 
@@ -624,7 +709,7 @@ fn execute_jump(cpu: &mut Cpu, memory_bus: &mut Memory, condition: JumpCondition
     let condition_met = check_jump_condition(cpu, condition);
 
     if condition_met {
-        let address = memory_bus.read16(cpu.registers.program_counter + 1);
+        let address = memory_bus.cpu_read16(cpu.registers.program_counter + 1);
         ExecutionStep::new(address, 4)
     } else {
         ExecutionStep::new(cpu.registers.program_counter.overflowing_add(3).0, 3)
@@ -643,7 +728,7 @@ fn execute_relative_jump(
     let condition_met = check_jump_condition(cpu, condition);
 
     if condition_met {
-        let offset = memory_bus.read_signed(cpu.registers.program_counter + 1);
+        let offset = memory_bus.cpu_read_signed(cpu.registers.program_counter + 1);
 
         let address = cpu.registers.program_counter.wrapping_add(offset as u16);
 
@@ -667,10 +752,10 @@ fn exeute_load(
         LoadTarget::E => cpu.registers.e,
         LoadTarget::H => cpu.registers.h,
         LoadTarget::L => cpu.registers.l,
-        LoadTarget::HL => memory_bus.read(cpu.registers.get_hl()),
+        LoadTarget::HL => memory_bus.cpu_read(cpu.registers.get_hl()),
         LoadTarget::ImmediateAddress => {
-            let address = memory_bus.read16(cpu.registers.program_counter + 1);
-            memory_bus.read(address)
+            let address = memory_bus.cpu_read16(cpu.registers.program_counter + 1);
+            memory_bus.cpu_read(address)
         }
     };
 
@@ -682,10 +767,10 @@ fn exeute_load(
         LoadTarget::E => cpu.registers.e = value,
         LoadTarget::H => cpu.registers.h = value,
         LoadTarget::L => cpu.registers.l = value,
-        LoadTarget::HL => memory_bus.write(cpu.registers.get_hl(), value),
+        LoadTarget::HL => memory_bus.cpu_write(cpu.registers.get_hl(), value),
         LoadTarget::ImmediateAddress => {
-            let address = memory_bus.read16(cpu.registers.program_counter + 1);
-            memory_bus.write(address, value)
+            let address = memory_bus.cpu_read16(cpu.registers.program_counter + 1);
+            memory_bus.cpu_write(address, value)
         }
     };
 
@@ -715,7 +800,7 @@ fn execute_load_immediate(
     memory_bus: &mut Memory,
     destination: LoadTarget,
 ) -> ExecutionStep {
-    let value = memory_bus.read(cpu.registers.program_counter + 1);
+    let value = memory_bus.cpu_read(cpu.registers.program_counter + 1);
 
     match destination {
         LoadTarget::A => cpu.registers.a = value,
@@ -725,7 +810,7 @@ fn execute_load_immediate(
         LoadTarget::E => cpu.registers.e = value,
         LoadTarget::H => cpu.registers.h = value,
         LoadTarget::L => cpu.registers.l = value,
-        LoadTarget::HL => memory_bus.write(cpu.registers.get_hl(), value),
+        LoadTarget::HL => memory_bus.cpu_write(cpu.registers.get_hl(), value),
         LoadTarget::ImmediateAddress => (),
     };
 
@@ -758,7 +843,7 @@ fn execute_read_from_ram(
         }
     };
 
-    let value = memory_bus.read(address);
+    let value = memory_bus.cpu_read(address);
     cpu.registers.a = value;
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(1), 2)
@@ -785,7 +870,7 @@ fn execute_write_to_ram(
     };
 
     let value = cpu.registers.a;
-    memory_bus.write(address, value);
+    memory_bus.cpu_write(address, value);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(1), 2)
 }
@@ -794,9 +879,9 @@ fn execute_write_to_ram_from_stack_pointer(
     cpu: &mut Cpu,
     memory_bus: &mut Memory,
 ) -> ExecutionStep {
-    let address = memory_bus.read16(cpu.registers.program_counter.wrapping_add(1));
+    let address = memory_bus.cpu_read16(cpu.registers.program_counter.wrapping_add(1));
 
-    memory_bus.write16(address, cpu.registers.stack_pointer);
+    memory_bus.cpu_write16(address, cpu.registers.stack_pointer);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(3), 5)
 }
@@ -806,7 +891,7 @@ fn execute_load_immediate16(
     memory_bus: &mut Memory,
     target: LoadTarget16,
 ) -> ExecutionStep {
-    let value = memory_bus.read16(cpu.registers.program_counter + 1);
+    let value = memory_bus.cpu_read16(cpu.registers.program_counter + 1);
 
     match target {
         LoadTarget16::BC => cpu.registers.set_bc(value),
@@ -833,8 +918,6 @@ fn execute_increment(
         new_value
     }
 
-    let hl_value = memory_bus.read(cpu.registers.get_hl());
-
     match target {
         ArithmeticTarget::A => cpu.registers.a = increment(cpu, cpu.registers.a),
         ArithmeticTarget::B => cpu.registers.b = increment(cpu, cpu.registers.b),
@@ -844,8 +927,9 @@ fn execute_increment(
         ArithmeticTarget::H => cpu.registers.h = increment(cpu, cpu.registers.h),
         ArithmeticTarget::L => cpu.registers.l = increment(cpu, cpu.registers.l),
         ArithmeticTarget::HL => {
+            let hl_value = memory_bus.cpu_read(cpu.registers.get_hl());
             let new_value = increment(cpu, hl_value);
-            memory_bus.write(cpu.registers.get_hl(), new_value)
+            memory_bus.cpu_write(cpu.registers.get_hl(), new_value)
         }
         ArithmeticTarget::Immediate => (),
     };
@@ -853,7 +937,7 @@ fn execute_increment(
     ExecutionStep::new(
         cpu.registers.program_counter.wrapping_add(1),
         match target {
-            ArithmeticTarget::HL => 2,
+            ArithmeticTarget::HL => 3,
             _ => 1,
         },
     )
@@ -874,8 +958,6 @@ fn execute_decrement(
         new_value
     }
 
-    let hl_value = memory_bus.read(cpu.registers.get_hl());
-
     match target {
         ArithmeticTarget::A => cpu.registers.a = decrement(cpu, cpu.registers.a),
         ArithmeticTarget::B => cpu.registers.b = decrement(cpu, cpu.registers.b),
@@ -885,8 +967,9 @@ fn execute_decrement(
         ArithmeticTarget::H => cpu.registers.h = decrement(cpu, cpu.registers.h),
         ArithmeticTarget::L => cpu.registers.l = decrement(cpu, cpu.registers.l),
         ArithmeticTarget::HL => {
+            let hl_value = memory_bus.cpu_read(cpu.registers.get_hl());
             let new_value = decrement(cpu, hl_value);
-            memory_bus.write(cpu.registers.get_hl(), new_value)
+            memory_bus.cpu_write(cpu.registers.get_hl(), new_value)
         }
         ArithmeticTarget::Immediate => (),
     };
@@ -894,7 +977,7 @@ fn execute_decrement(
     ExecutionStep::new(
         cpu.registers.program_counter.wrapping_add(1),
         match target {
-            ArithmeticTarget::HL => 2,
+            ArithmeticTarget::HL => 3,
             _ => 1,
         },
     )
@@ -1031,9 +1114,14 @@ fn execute_complement_carry_flag(cpu: &mut Cpu) -> ExecutionStep {
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(1), 1)
 }
 
-fn execute_stop(cpu: &mut Cpu) -> ! {
-    println!("{}", cpu);
-    panic!("STOP!");
+/// STOP is only supported to switch the CGB speed, it is encoded on 2 bytes.
+fn execute_stop(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
+    // Without a speed switch armed, STOP waits for a joypad input
+    if !memory_bus.switch_speed() {
+        cpu.stopped = true;
+    }
+
+    ExecutionStep::new(cpu.registers.program_counter.wrapping_add(2), 1)
 }
 
 fn execute_disable_interrupts(cpu: &mut Cpu) -> ExecutionStep {
@@ -1084,9 +1172,9 @@ fn execute_pop(cpu: &mut Cpu, memory_bus: &mut Memory, target: PushPopTarget) ->
 
 fn execute_call(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
     let pc = cpu.registers.program_counter;
-    cpu.push(memory_bus, pc.wrapping_add(3));
+    let address = memory_bus.cpu_read16(pc.wrapping_add(1));
 
-    let address = memory_bus.read16(cpu.registers.program_counter.wrapping_add(1));
+    cpu.push(memory_bus, pc.wrapping_add(3));
 
     ExecutionStep::new(address, 6)
 }
@@ -1141,43 +1229,43 @@ fn execute_restart(cpu: &mut Cpu, memory_bus: &mut Memory, address: u8) -> Execu
 
 fn execute_extended_opcode(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
     cpu.registers.program_counter = cpu.registers.program_counter.wrapping_add(1);
-    let opcode = memory_bus.read(cpu.registers.program_counter);
+    let opcode = memory_bus.cpu_read(cpu.registers.program_counter);
     let instruction = Instruction::from_byte_extended(opcode);
 
     cpu.execute(memory_bus, instruction)
 }
 
 fn execute_load_h(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
-    let half_address = memory_bus.read(cpu.registers.program_counter.wrapping_add(1));
-    cpu.registers.a = memory_bus.read(half_address as u16 + 0xFF00);
+    let half_address = memory_bus.cpu_read(cpu.registers.program_counter.wrapping_add(1));
+    cpu.registers.a = memory_bus.cpu_read(half_address as u16 + 0xFF00);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(2), 3)
 }
 
 fn execute_write_h(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
-    let half_address = memory_bus.read(cpu.registers.program_counter.wrapping_add(1));
-    memory_bus.write(half_address as u16 + 0xFF00, cpu.registers.a);
+    let half_address = memory_bus.cpu_read(cpu.registers.program_counter.wrapping_add(1));
+    memory_bus.cpu_write(half_address as u16 + 0xFF00, cpu.registers.a);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(2), 3)
 }
 
 fn execute_load_hc(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
     let half_address = cpu.registers.c;
-    cpu.registers.a = memory_bus.read(half_address as u16 + 0xFF00);
+    cpu.registers.a = memory_bus.cpu_read(half_address as u16 + 0xFF00);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(1), 2)
 }
 
 fn execute_write_hc(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
     let half_address = cpu.registers.c;
-    memory_bus.write(half_address as u16 + 0xFF00, cpu.registers.a);
+    memory_bus.cpu_write(half_address as u16 + 0xFF00, cpu.registers.a);
 
     ExecutionStep::new(cpu.registers.program_counter.wrapping_add(1), 2)
 }
 
 fn execute_add_sp(cpu: &mut Cpu, memory_bus: &mut Memory) -> ExecutionStep {
     let offset =
-        memory_bus.read_signed(cpu.registers.program_counter.wrapping_add(1)) as i16 as u16;
+        memory_bus.cpu_read_signed(cpu.registers.program_counter.wrapping_add(1)) as i16 as u16;
     let sp = cpu.registers.stack_pointer;
 
     cpu.registers.stack_pointer = sp.wrapping_add(offset);
@@ -1328,7 +1416,7 @@ fn execute_swap(
     target: ByteArithmeticTarget,
 ) -> ExecutionStep {
     fn swap(cpu: &mut Cpu, value: u8) -> u8 {
-        let new_value = (value >> 4) | (value << 4);
+        let new_value = value.rotate_left(4);
 
         cpu.registers.f.zero = new_value == 0;
         cpu.registers.f.subtract = false;
@@ -1463,25 +1551,23 @@ fn execute_interrupts(
     }
 
     let interrupt = interrupts.get_highest_priority_interrupt();
-    let target = if let Some(i) = interrupt {
-        Some(match i {
-            Interrupt::VBlank => 0x40,
-            Interrupt::LcdStat => 0x48,
-            Interrupt::Timer => 0x50,
-            Interrupt::Serial => 0x58,
-            Interrupt::Joypad => 0x60,
-        })
-    } else {
-        None
-    };
+    let target = interrupt.map(|i| match i {
+        Interrupt::VBlank => 0x40,
+        Interrupt::LcdStat => 0x48,
+        Interrupt::Timer => 0x50,
+        Interrupt::Serial => 0x58,
+        Interrupt::Joypad => 0x60,
+    });
 
     target.zip(interrupt).map(|(target, interrupt)| {
         cpu.ime = false;
 
-        cpu.registers.stack_pointer = cpu.registers.stack_pointer.wrapping_sub(2);
-        memory_bus.write16(cpu.registers.stack_pointer, cpu.registers.program_counter);
+        // 2 internal M-cycles, the push takes another one and 2 writes
+        memory_bus.cpu_tick();
+        let pc = cpu.registers.program_counter;
+        cpu.push(memory_bus, pc);
 
         interrupts.ack_interrupt(interrupt, memory_bus);
-        ExecutionStep::new(target, 3)
+        ExecutionStep::new(target, 5)
     })
 }
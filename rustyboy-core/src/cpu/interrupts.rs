@@ -89,12 +89,12 @@ impl std::convert::From<(u8, u8)> for Interrupts {
 impl std::convert::From<&mut Interrupts> for (u8, u8) {
     fn from(interrupts: &mut Interrupts) -> (u8, u8) {
         (
-            (interrupts.vblank.enabled as u8) << 0
+            (interrupts.vblank.enabled as u8)
                 | (interrupts.lcd_stat.enabled as u8) << 1
                 | (interrupts.timer.enabled as u8) << 2
                 | (interrupts.serial.enabled as u8) << 3
                 | (interrupts.joypad.enabled as u8) << 4,
-            (interrupts.vblank.flag as u8) << 0
+            (interrupts.vblank.flag as u8)
                 | (interrupts.lcd_stat.flag as u8) << 1
                 | (interrupts.timer.flag as u8) << 2
                 | (interrupts.serial.flag as u8) << 3
@@ -0,0 +1,309 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+
+use super::instructions::Instruction;
+use crate::memory::Memory;
+
+const ENTRY_POINT: u16 = 0x100;
+// Call tree nodes taking less than this share of the total cycles are hidden
+// from the hierarchical report to keep it readable.
+const MIN_TREE_PERCENT: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunctionAddress {
+    pub bank: u16,
+    pub address: u16,
+}
+
+impl FunctionAddress {
+    pub(crate) fn resolve(memory_bus: &Memory, address: u16) -> FunctionAddress {
+        FunctionAddress {
+            bank: memory_bus.cartridge.rom_bank(address),
+            address,
+        }
+    }
+}
+
+impl std::fmt::Display for FunctionAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.address)
+    }
+}
+
+/// Instructions that modify the shadow call stack.
+pub enum StackEvent {
+    Call,
+    Return,
+}
+
+impl StackEvent {
+    pub fn from_instruction(instruction: &Instruction) -> Option<StackEvent> {
+        match instruction {
+            Instruction::Call | Instruction::CallCondition(_) | Instruction::Restart(_) => {
+                Some(StackEvent::Call)
+            }
+            Instruction::Return
+            | Instruction::ReturnCondition(_)
+            | Instruction::ReturnAndEnableInterrupts => Some(StackEvent::Return),
+            _ => None,
+        }
+    }
+}
+
+struct StackFrame {
+    node: usize,
+    // Address of the return address pushed by the call
+    stack_pointer: u16,
+}
+
+struct CallNode {
+    function: FunctionAddress,
+    calls: u64,
+    self_cycles: u64,
+    children: HashMap<FunctionAddress, usize>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct FunctionStats {
+    calls: u64,
+    self_cycles: u64,
+    total_cycles: u64,
+}
+
+/// Keeps a shadow call stack built from CALL, RST, interrupt entries and
+/// RET/RETI, and accumulates the machine cycles spent in every function.
+pub struct Profiler {
+    stack: Vec<StackFrame>,
+    nodes: Vec<CallNode>,
+    symbols: HashMap<FunctionAddress, String>,
+    total_cycles: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            stack: vec![],
+            nodes: vec![CallNode {
+                function: FunctionAddress {
+                    bank: 0,
+                    address: ENTRY_POINT,
+                },
+                calls: 1,
+                self_cycles: 0,
+                children: HashMap::new(),
+            }],
+            symbols: HashMap::new(),
+            total_cycles: 0,
+        }
+    }
+
+    /// Loads function names from a RGBDS/no$gmb style `.sym` file
+    /// (`BB:AAAA name` lines, `;` starts a comment).
+    pub fn load_symbols(&mut self, path: &str) -> Result<()> {
+        let content = fs::read_to_string(path)?;
+
+        for line in content.lines() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            let (location, name) = match line.split_once(char::is_whitespace) {
+                Some(parts) => parts,
+                None => continue,
+            };
+            let (bank, address) = match location.split_once(':') {
+                Some(parts) => parts,
+                None => continue,
+            };
+
+            let bank = u16::from_str_radix(bank, 16)?;
+            let address = u16::from_str_radix(address, 16)?;
+            self.symbols
+                .insert(FunctionAddress { bank, address }, name.trim().to_string());
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn enter(&mut self, function: FunctionAddress, stack_pointer: u16) {
+        // Frames at or below the new return address were abandoned without a RET
+        self.unwind(stack_pointer);
+
+        let parent = self.current_node();
+        let node = match self.nodes[parent].children.get(&function) {
+            Some(&node) => node,
+            None => {
+                self.nodes.push(CallNode {
+                    function,
+                    calls: 0,
+                    self_cycles: 0,
+                    children: HashMap::new(),
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent].children.insert(function, node);
+                node
+            }
+        };
+
+        self.nodes[node].calls += 1;
+        self.stack.push(StackFrame {
+            node,
+            stack_pointer,
+        });
+    }
+
+    pub(crate) fn leave(&mut self, stack_pointer: u16) {
+        self.unwind(stack_pointer);
+    }
+
+    pub(crate) fn add_cycles(&mut self, cycles: u8) {
+        let node = self.current_node();
+        self.nodes[node].self_cycles += cycles as u64;
+        self.total_cycles += cycles as u64;
+    }
+
+    fn unwind(&mut self, stack_pointer: u16) {
+        while let Some(frame) = self.stack.last() {
+            if frame.stack_pointer > stack_pointer {
+                break;
+            }
+            self.stack.pop();
+        }
+    }
+
+    fn current_node(&self) -> usize {
+        self.stack.last().map_or(0, |frame| frame.node)
+    }
+
+    fn function_name(&self, function: &FunctionAddress) -> String {
+        match self.symbols.get(function) {
+            Some(name) => format!("{} ({})", name, function),
+            None => function.to_string(),
+        }
+    }
+
+    fn percent(&self, cycles: u64) -> f64 {
+        if self.total_cycles == 0 {
+            0.0
+        } else {
+            cycles as f64 * 100.0 / self.total_cycles as f64
+        }
+    }
+
+    fn node_total_cycles(&self, node: usize) -> u64 {
+        let node = &self.nodes[node];
+        node.self_cycles
+            + node
+                .children
+                .values()
+                .map(|&child| self.node_total_cycles(child))
+                .sum::<u64>()
+    }
+
+    fn collect_function_stats(
+        &self,
+        node: usize,
+        active: &mut HashSet<FunctionAddress>,
+        stats: &mut HashMap<FunctionAddress, FunctionStats>,
+    ) {
+        let call_node = &self.nodes[node];
+        let function_stats = stats.entry(call_node.function).or_default();
+        function_stats.calls += call_node.calls;
+        function_stats.self_cycles += call_node.self_cycles;
+
+        // Recursive calls are already accounted in the outermost frame
+        let outermost = active.insert(call_node.function);
+        if outermost {
+            function_stats.total_cycles += self.node_total_cycles(node);
+        }
+
+        for &child in call_node.children.values() {
+            self.collect_function_stats(child, active, stats);
+        }
+
+        if outermost {
+            active.remove(&call_node.function);
+        }
+    }
+
+    /// Per-function report sorted by the cycles spent in the function body.
+    pub fn flat_report(&self) -> String {
+        let mut stats = HashMap::new();
+        self.collect_function_stats(0, &mut HashSet::new(), &mut stats);
+
+        let mut functions: Vec<_> = stats.into_iter().collect();
+        functions.sort_by(|(a_function, a), (b_function, b)| {
+            b.self_cycles
+                .cmp(&a.self_cycles)
+                .then(a_function.cmp(b_function))
+        });
+
+        let mut report = String::new();
+        writeln!(
+            report,
+            "{:<40} {:>10} {:>14} {:>7} {:>14} {:>7}",
+            "Function", "Calls", "Self cycles", "Self%", "Total cycles", "Total%"
+        )
+        .unwrap();
+        for (function, stats) in functions {
+            writeln!(
+                report,
+                "{:<40} {:>10} {:>14} {:>6.2}% {:>14} {:>6.2}%",
+                self.function_name(&function),
+                stats.calls,
+                stats.self_cycles,
+                self.percent(stats.self_cycles),
+                stats.total_cycles,
+                self.percent(stats.total_cycles)
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    /// Call tree report, every node shows the cycles spent in it and its callees.
+    pub fn tree_report(&self) -> String {
+        let mut report = String::new();
+        self.write_tree_node(&mut report, 0, 0);
+        report
+    }
+
+    fn write_tree_node(&self, report: &mut String, node: usize, depth: usize) {
+        let total_cycles = self.node_total_cycles(node);
+        if node != 0 && self.percent(total_cycles) < MIN_TREE_PERCENT {
+            return;
+        }
+
+        let call_node = &self.nodes[node];
+        writeln!(
+            report,
+            "{:indent$}{} calls={} total={} ({:.2}%) self={} ({:.2}%)",
+            "",
+            self.function_name(&call_node.function),
+            call_node.calls,
+            total_cycles,
+            self.percent(total_cycles),
+            call_node.self_cycles,
+            self.percent(call_node.self_cycles),
+            indent = depth * 2
+        )
+        .unwrap();
+
+        let mut children: Vec<_> = call_node
+            .children
+            .values()
+            .map(|&child| (self.node_total_cycles(child), child))
+            .collect();
+        children.sort_by(|a, b| b.cmp(a));
+
+        for (_, child) in children {
+            self.write_tree_node(report, child, depth + 1);
+        }
+    }
+}
+
+impl std::default::Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
@@ -0,0 +1,320 @@
+pub mod linked;
+
+use anyhow::Result;
+
+use crate::cpu::profiler::Profiler;
+use crate::cpu::Registers;
+use crate::joypad::{JoypadKey, JoypadState};
+use crate::model::{Model, Speed};
+use crate::ppu::palette::Color;
+use crate::serial::SerialDevice;
+use crate::sgb::{Sgb, SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
+use crate::{cartridge::Cartridge, cpu::Cpu, memory::Memory, ppu::Ppu};
+
+/// Options used to create a [`Hardware`] instance, see [`Hardware::builder`].
+pub struct HardwareBuilder {
+    cartridge: Cartridge,
+    tracing: bool,
+    profiling: bool,
+    symbols_path: Option<String>,
+    access_restrictions: bool,
+    model: Option<Model>,
+    serial_echo: bool,
+}
+
+impl HardwareBuilder {
+    /// Prints the CPU registers after every instruction.
+    pub fn tracing(mut self, enabled: bool) -> Self {
+        self.tracing = enabled;
+        self
+    }
+
+    /// Tracks the call stack and the cycles spent in every function.
+    pub fn profiling(mut self, enabled: bool) -> Self {
+        self.profiling = enabled;
+        self
+    }
+
+    /// Symbol file used to name functions in the profiler reports, when profiling is enabled.
+    pub fn symbols(mut self, path: &str) -> Self {
+        self.symbols_path = Some(path.to_string());
+        self
+    }
+
+    /// Locks the CPU out of VRAM during pixel transfer and out of OAM during
+    /// OAM search and pixel transfer, as on hardware. Enabled by default.
+    pub fn access_restrictions(mut self, enabled: bool) -> Self {
+        self.access_restrictions = enabled;
+        self
+    }
+
+    /// Console model to emulate, by default it is picked from the cartridge header.
+    pub fn model(mut self, model: Model) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Prints the bytes sent through the serial port to stdout.
+    pub fn serial_echo(mut self, enabled: bool) -> Self {
+        self.serial_echo = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<Hardware> {
+        let model = self
+            .model
+            .unwrap_or_else(|| Model::for_cartridge(&self.cartridge));
+        let mut hardware = Hardware::with_model(self.cartridge, model);
+        hardware.set_access_restrictions(self.access_restrictions);
+        hardware.memory_bus.io_registers.serial.echo = self.serial_echo;
+
+        if self.tracing {
+            hardware.enable_tracing();
+        }
+        if self.profiling {
+            hardware.enable_profiling(self.symbols_path.as_deref())?;
+        }
+
+        Ok(hardware)
+    }
+}
+
+pub struct Hardware {
+    cpu: Cpu,
+    ppu: Ppu,
+    memory_bus: Memory,
+    joypad: JoypadState,
+    sgb: Option<Sgb>,
+    /// Dots elapsed since power on, the PPU clock runs at a fixed rate
+    dots: u64,
+    tracing_enabled: bool,
+}
+
+impl Hardware {
+    pub fn new(cartridge: Cartridge) -> Hardware {
+        let model = Model::for_cartridge(&cartridge);
+        Hardware::with_model(cartridge, model)
+    }
+
+    fn with_model(cartridge: Cartridge, model: Model) -> Hardware {
+        let memory_bus = Memory::new(cartridge, model);
+        let cpu = Cpu::new(model);
+        let ppu = Ppu::new();
+        let joypad = JoypadState::new();
+        let sgb = model.is_sgb().then(Sgb::new);
+
+        Hardware {
+            cpu,
+            ppu,
+            memory_bus,
+            joypad,
+            sgb,
+            dots: 0,
+            tracing_enabled: false,
+        }
+    }
+
+    pub fn builder(cartridge: Cartridge) -> HardwareBuilder {
+        HardwareBuilder {
+            cartridge,
+            tracing: false,
+            profiling: false,
+            symbols_path: None,
+            access_restrictions: true,
+            model: None,
+            serial_echo: false,
+        }
+    }
+
+    /// Runs the emulation until the PPU completes a frame and returns it.
+    pub fn run(&mut self) -> [Color; 160 * 144] {
+        while !self.step() {}
+        *self.frame()
+    }
+
+    /// Executes a single CPU instruction and advances the rest of the hardware
+    /// by the same amount of cycles.
+    ///
+    /// Returns `true` if the PPU completed a frame during the step, see [`Hardware::frame`].
+    pub fn step(&mut self) -> bool {
+        // The step is timed at the speed the CPU had when it started, STOP may switch it
+        let speed = self.memory_bus.speed;
+        let (cpu_cycles, _) = self.cpu.step(&mut self.memory_bus);
+        // VRAM DMA stalls the CPU while the rest of the hardware keeps running
+        let elapsed_cycles = cpu_cycles as u16 + self.memory_bus.vram_dma_step();
+
+        self.joypad.update_keys_status(&mut self.memory_bus);
+
+        // CPU clock domain: the CPU already ran the M-cycles up to its last
+        // memory access, OAM DMA, the timer and the serial port catch up
+        let instruction_cycles = self.memory_bus.take_instruction_cycles();
+        debug_assert!(instruction_cycles <= elapsed_cycles);
+        for _ in instruction_cycles..elapsed_cycles {
+            self.memory_bus.cpu_cycle();
+        }
+
+        // PPU clock domain: dots run at a fixed rate whatever the CPU speed
+        let mut frame_completed = false;
+        let elapsed_dots = elapsed_cycles * speed.dots_per_cycle();
+        self.dots += elapsed_dots as u64;
+        for _ in 0..elapsed_dots {
+            if self.ppu.step(&mut self.memory_bus) {
+                frame_completed = true;
+            }
+        }
+
+        self.ppu.update_memory(&mut self.memory_bus);
+
+        if let Some(sgb) = &mut self.sgb {
+            if let Some(packets) = &mut self.memory_bus.io_registers.sgb_packets {
+                for command in packets.commands.drain(..) {
+                    sgb.execute(&command);
+                }
+            }
+            if frame_completed {
+                sgb.colorize(&mut self.ppu.buffer, self.ppu.frame_rendered);
+            }
+        }
+
+        if self.tracing_enabled {
+            println!(
+                "CPU: {} - {}",
+                self.cpu.registers, self.memory_bus.cartridge.mbc
+            );
+        }
+
+        frame_completed
+    }
+
+    /// Last frame completed by the PPU, until it starts drawing the next one.
+    pub fn frame(&self) -> &[Color; 160 * 144] {
+        &self.ppu.buffer
+    }
+
+    pub fn model(&self) -> Model {
+        self.memory_bus.model
+    }
+
+    /// Current CPU speed, only CGB can switch to double speed.
+    pub fn speed(&self) -> Speed {
+        self.memory_bus.speed
+    }
+
+    /// In SGB mode, the last frame inside the SGB border.
+    pub fn sgb_screen(&self) -> Option<&[Color; SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT]> {
+        self.sgb.as_ref().map(|sgb| &*sgb.screen)
+    }
+
+    /// Time elapsed since power on, in dots (4 MHz), whatever the CPU speed.
+    pub fn dots(&self) -> u64 {
+        self.dots
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.cpu.registers.program_counter
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.cpu.registers
+    }
+
+    /// Reads a byte on the memory bus for a debugger, an OAM DMA in progress
+    /// doesn't lock it out.
+    pub fn read_memory(&self, address: u16) -> u8 {
+        self.memory_bus.debug_read(address)
+    }
+
+    /// Bytes sent through the serial port so far, whatever device is plugged in.
+    pub fn serial_output(&self) -> &[u8] {
+        self.memory_bus.io_registers.serial.output()
+    }
+
+    /// Plugs a device into the serial port, replacing the previous one.
+    pub fn connect_serial(&mut self, device: Box<dyn SerialDevice>) {
+        self.memory_bus.io_registers.serial.connect(device);
+    }
+
+    pub fn button_pressed(&mut self, button: JoypadKey) {
+        self.joypad.set_key_pressed(button);
+    }
+
+    pub fn button_released(&mut self, button: JoypadKey) {
+        self.joypad.set_key_released(button);
+    }
+
+    /// Enables or disables the VRAM and OAM access restrictions, see
+    /// [`HardwareBuilder::access_restrictions`].
+    pub fn set_access_restrictions(&mut self, enabled: bool) {
+        self.memory_bus.access_restrictions = enabled;
+    }
+
+    pub fn enable_tracing(&mut self) {
+        self.tracing_enabled = true;
+    }
+
+    pub fn enable_profiling(&mut self, symbols_path: Option<&str>) -> Result<()> {
+        let mut profiler = Profiler::new();
+        if let Some(path) = symbols_path {
+            profiler.load_symbols(path)?;
+        }
+        self.cpu.profiler = Some(profiler);
+
+        Ok(())
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.cpu.profiler.as_ref()
+    }
+}
+
+impl Drop for Hardware {
+    fn drop(&mut self) {
+        if self.tracing_enabled {
+            println!("CPU: {}", self.cpu.registers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn hardware_can_move_to_another_thread() {
+        assert_send::<Hardware>();
+        assert_send::<linked::LinkedHardware>();
+    }
+
+    #[test]
+    fn stop_waits_for_a_joypad_input() {
+        let mut data = vec![0; 0x8000];
+        // NOP; JP 0x0150
+        data[0x100..0x104].copy_from_slice(&[0x00, 0xc3, 0x50, 0x01]);
+        #[rustfmt::skip]
+        let program = [
+            0xf3,             // DI
+            0x3e, 0x10,       // LD A,0x10
+            0xe0, 0x00,       // LDH (JOYP),A
+            0x10, 0x00,       // STOP
+            0x3e, 0x42,       // LD A,0x42
+            0xe0, 0x80,       // LDH (0xff80),A
+            0x18, 0xfe,       // JR -2
+        ];
+        data[0x150..0x150 + program.len()].copy_from_slice(&program);
+        let mut hardware = Hardware::new(Cartridge::from_bytes(data).unwrap());
+
+        for _ in 0..1000 {
+            hardware.step();
+        }
+        assert_eq!(hardware.program_counter(), 0x157);
+        assert_eq!(hardware.read_memory(0xff80), 0);
+
+        hardware.button_pressed(JoypadKey::A);
+        for _ in 0..10 {
+            hardware.step();
+        }
+        assert_eq!(hardware.read_memory(0xff80), 0x42);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::ppu::palette::Color;
+use crate::serial::cable::cable;
+
+use super::Hardware;
+
+/// Two Gameboys plugged together with an in-memory link cable.
+///
+/// They run in lockstep on the same clock: each step executes an instruction
+/// on the one lagging behind, so a run is reproducible.
+pub struct LinkedHardware {
+    pub first: Hardware,
+    pub second: Hardware,
+}
+
+impl LinkedHardware {
+    pub fn new(mut first: Hardware, mut second: Hardware) -> LinkedHardware {
+        let (first_end, second_end) = cable();
+        first.connect_serial(Box::new(first_end));
+        second.connect_serial(Box::new(second_end));
+
+        LinkedHardware { first, second }
+    }
+
+    /// Executes a single CPU instruction on the Gameboy lagging behind, the
+    /// first one on ties.
+    ///
+    /// Returns whether each Gameboy completed a frame during the step.
+    pub fn step(&mut self) -> (bool, bool) {
+        if self.first.dots() <= self.second.dots() {
+            (self.first.step(), false)
+        } else {
+            (false, self.second.step())
+        }
+    }
+
+    /// Runs the emulation until both PPUs complete a frame and returns the
+    /// last frame of each Gameboy.
+    pub fn run(&mut self) -> ([Color; 160 * 144], [Color; 160 * 144]) {
+        let mut first_frame = None;
+        let mut second_frame = None;
+        loop {
+            match self.step() {
+                (true, _) => first_frame = Some(*self.first.frame()),
+                (_, true) => second_frame = Some(*self.second.frame()),
+                _ => {}
+            }
+
+            if let (Some(first), Some(second)) = (&first_frame, &second_frame) {
+                return (*first, *second);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    const RECEIVED_ADDRESS: u16 = 0xff80;
+
+    /// ROM sending `byte` with the given SC value, then storing the byte
+    /// received at 0xff80.
+    fn transfer_rom(byte: u8, control: u8) -> Hardware {
+        let mut data = vec![0; 0x8000];
+        // NOP; JP 0x0150
+        data[0x100..0x104].copy_from_slice(&[0x00, 0xc3, 0x50, 0x01]);
+
+        #[rustfmt::skip]
+        let mut program = vec![
+            0xf3,             // DI
+            0x3e, byte,       // LD A,byte
+            0xe0, 0x01,       // LDH (SB),A
+        ];
+        if control == 0x81 {
+            // Leaves time for the other Gameboy to wait for the transfer
+            #[rustfmt::skip]
+            program.extend([
+                0x06, 0x00,   // LD B,0
+                0x05,         // delay: DEC B
+                0x20, 0xfd,   // JR NZ,delay
+            ]);
+        }
+        #[rustfmt::skip]
+        program.extend([
+            0x3e, control,    // LD A,control
+            0xe0, 0x02,       // LDH (SC),A
+            0xf0, 0x02,       // wait: LDH A,(SC)
+            0xcb, 0x7f,       // BIT 7,A
+            0x20, 0xfa,       // JR NZ,wait
+            0xf0, 0x01,       // LDH A,(SB)
+            0xe0, 0x80,       // LDH (0xff80),A
+            0x18, 0xfe,       // JR -2
+        ]);
+        data[0x150..0x150 + program.len()].copy_from_slice(&program);
+
+        Hardware::new(Cartridge::from_bytes(data).unwrap())
+    }
+
+    fn linked() -> LinkedHardware {
+        LinkedHardware::new(transfer_rom(0x42, 0x81), transfer_rom(0x99, 0x80))
+    }
+
+    /// The transfer takes 8 bit periods of 128 M-cycles, well within that.
+    fn run_transfer(linked: &mut LinkedHardware) {
+        for _ in 0..10_000 {
+            linked.step();
+        }
+    }
+
+    /// Time and program counter of both Gameboys after the transfer.
+    fn transfer_state() -> (u64, u64, u16, u16) {
+        let mut linked = linked();
+        run_transfer(&mut linked);
+
+        (
+            linked.first.dots(),
+            linked.second.dots(),
+            linked.first.program_counter(),
+            linked.second.program_counter(),
+        )
+    }
+
+    #[test]
+    fn linked_gameboys_exchange_bytes() {
+        let mut linked = linked();
+        run_transfer(&mut linked);
+
+        assert_eq!(linked.first.read_memory(RECEIVED_ADDRESS), 0x99);
+        assert_eq!(linked.second.read_memory(RECEIVED_ADDRESS), 0x42);
+        assert_eq!(linked.first.serial_output(), &[0x42]);
+        assert_eq!(linked.second.serial_output(), &[0x99]);
+    }
+
+    #[test]
+    fn linked_gameboys_run_in_lockstep() {
+        let mut linked = linked();
+        for _ in 0..10_000 {
+            linked.step();
+            // An instruction lasts 6 M-cycles at most
+            assert!(linked.first.dots().abs_diff(linked.second.dots()) <= 24);
+        }
+    }
+
+    #[test]
+    fn linked_runs_are_reproducible() {
+        assert_eq!(transfer_state(), transfer_state());
+    }
+}
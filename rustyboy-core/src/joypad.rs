@@ -2,7 +2,7 @@ use std::collections::HashSet;
 
 use crate::memory::Memory;
 
-const JOYPAD_STATE_ADDRESS: u16 = 0xff00;
+pub const JOYPAD_STATE_ADDRESS: u16 = 0xff00;
 
 pub struct JoypadState {
     keys: HashSet<JoypadKey>,
@@ -79,6 +79,6 @@ impl JoypadState {
         //     println!("New state: {:b}", new_state);
         // }
 
-        memory_bus.write(JOYPAD_STATE_ADDRESS, new_state)
+        memory_bus.io_registers.set_joypad_inputs(new_state)
     }
 }
@@ -10,7 +10,7 @@ pub enum ObjectSize {
 
 #[derive(Debug)]
 pub struct LcdControl {
-    enabled: bool,
+    pub enabled: bool,
     window_tile_map_area: bool,
     pub window_enabled: bool,
     pub background_window_tile_data_area: bool,
@@ -0,0 +1,32 @@
+//! Gameboy emulator core.
+//!
+//! [`Hardware`] wires together the CPU, PPU and memory bus for a [`Cartridge`]
+//! and produces one frame of [`Color`]s per call to [`Hardware::run`]. In SGB
+//! mode, [`Hardware::sgb_screen`] also shows the frame inside its border.
+
+mod cartridge;
+mod cpu;
+mod hardware;
+mod joypad;
+mod lcd;
+mod memory;
+mod model;
+mod ppu;
+mod screenshot;
+mod serial;
+mod sgb;
+mod utils;
+
+pub use cartridge::{Cartridge, CartridgeHeader};
+pub use cpu::profiler::{FunctionAddress, Profiler};
+pub use cpu::{FlagsRegister, Registers};
+pub use hardware::linked::LinkedHardware;
+pub use hardware::{Hardware, HardwareBuilder};
+pub use joypad::JoypadKey;
+pub use model::{Model, Speed};
+pub use ppu::palette::{Color, DisplayPalette};
+pub use screenshot::{save_png, save_sgb_png, write_png, write_sgb_png};
+pub use serial::cable::{cable, CableEnd};
+pub use serial::link::TcpLink;
+pub use serial::{Disconnected, SerialDevice};
+pub use sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
@@ -0,0 +1,58 @@
+pub const OAM_BASE_ADDRESS: u16 = 0xfe00;
+const OAM_SIZE: u16 = 0xa0;
+
+/// OAM DMA transfer, copying one byte per M-cycle.
+///
+/// A transfer starts one M-cycle after DMA is written. Writing DMA again
+/// while a transfer is running restarts it, the old transfer keeps going
+/// until the new one starts.
+pub struct OamDma {
+    pending_source: Option<u16>,
+    source: u16,
+    index: u16,
+    active: bool,
+}
+
+impl OamDma {
+    pub fn new() -> OamDma {
+        OamDma {
+            pending_source: None,
+            source: 0,
+            index: 0,
+            active: false,
+        }
+    }
+
+    pub fn request(&mut self, value: u8) {
+        self.pending_source = Some((value as u16) << 8);
+    }
+
+    /// Address of the next byte to copy while a transfer is running.
+    pub fn source(&self) -> Option<u16> {
+        self.active.then_some(self.source + self.index)
+    }
+
+    /// Advances the transfer by one M-cycle.
+    ///
+    /// Returns the source and destination addresses of the byte to copy, if any.
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        let transfer = if self.active {
+            let transfer = (self.source + self.index, OAM_BASE_ADDRESS + self.index);
+            self.index += 1;
+            if self.index == OAM_SIZE {
+                self.active = false;
+            }
+            Some(transfer)
+        } else {
+            None
+        };
+
+        if let Some(source) = self.pending_source.take() {
+            self.source = source;
+            self.index = 0;
+            self.active = true;
+        }
+
+        transfer
+    }
+}
@@ -0,0 +1,109 @@
+const VRAM_BASE_ADDRESS: u16 = 0x8000;
+const BLOCK_SIZE: u16 = 0x10;
+
+/// CGB VRAM DMA, set up through HDMA1-HDMA5, copying blocks of 16 bytes.
+///
+/// A general-purpose transfer copies everything at once while the CPU is
+/// stalled, an HBlank transfer copies one block at the start of every HBlank.
+pub struct VramDma {
+    source: u16,
+    destination: u16,
+    /// Blocks left to copy, the transfer is over when it reaches 0
+    remaining: u8,
+    /// Blocks that can be copied right away
+    pending: u8,
+    hblank_mode: bool,
+    active: bool,
+}
+
+impl VramDma {
+    pub fn new() -> VramDma {
+        VramDma {
+            source: 0,
+            destination: 0,
+            remaining: 0,
+            pending: 0,
+            hblank_mode: false,
+            active: false,
+        }
+    }
+
+    pub fn write_source_high(&mut self, value: u8) {
+        self.source = (self.source & 0x00ff) | (value as u16) << 8;
+    }
+
+    /// The lower 4 bits are ignored, blocks are aligned on 16 bytes.
+    pub fn write_source_low(&mut self, value: u8) {
+        self.source = (self.source & 0xff00) | (value & 0xf0) as u16;
+    }
+
+    /// Only the bits addressing VRAM are kept.
+    pub fn write_destination_high(&mut self, value: u8) {
+        self.destination = (self.destination & 0x00ff) | ((value & 0x1f) as u16) << 8;
+    }
+
+    pub fn write_destination_low(&mut self, value: u8) {
+        self.destination = (self.destination & 0x1f00) | (value & 0xf0) as u16;
+    }
+
+    /// HDMA5: bit 7 is clear while a transfer is running, the lower bits are
+    /// the remaining blocks minus one. It reads 0xff once a transfer is over.
+    pub fn read_control(&self) -> u8 {
+        let remaining = self.remaining.wrapping_sub(1) & 0x7f;
+        if self.active {
+            remaining
+        } else {
+            0x80 | remaining
+        }
+    }
+
+    /// HDMA5: starts a transfer of `(value & 0x7f) + 1` blocks, in HBlank mode
+    /// when bit 7 is set. Clearing bit 7 during an HBlank transfer cancels it.
+    ///
+    /// With the LCD off there is no HBlank to wait for, an HBlank transfer
+    /// copies its first block right away.
+    pub fn write_control(&mut self, value: u8, lcd_enabled: bool) {
+        let hblank_mode = value & 0x80 != 0;
+
+        if self.active && self.hblank_mode && !hblank_mode {
+            self.active = false;
+            self.pending = 0;
+            return;
+        }
+
+        self.remaining = (value & 0x7f) + 1;
+        self.hblank_mode = hblank_mode;
+        self.active = true;
+        self.pending = match (hblank_mode, lcd_enabled) {
+            (false, _) => self.remaining,
+            (true, true) => 0,
+            (true, false) => 1,
+        };
+    }
+
+    /// Called when the PPU enters HBlank, lets an HBlank transfer copy a block.
+    pub fn hblank(&mut self) {
+        if self.active && self.hblank_mode {
+            self.pending = 1;
+        }
+    }
+
+    /// Returns the source and destination addresses of the next block to copy, if any.
+    pub fn next_block(&mut self) -> Option<(u16, u16)> {
+        if self.pending == 0 {
+            return None;
+        }
+
+        let block = (self.source, VRAM_BASE_ADDRESS | self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & 0x1ff0;
+        self.pending -= 1;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.active = false;
+            self.pending = 0;
+        }
+
+        Some(block)
+    }
+}
@@ -0,0 +1,117 @@
+use super::{
+    timer::{Timer, DIV_ADDRESS, TAC_ADDRESS},
+    GeneralPourposeMemoryBank, MemoryBank,
+};
+use crate::serial::Serial;
+use crate::sgb::packets::PacketReceiver;
+
+const JOYP_ADDRESS: u16 = 0xff00;
+const SB_ADDRESS: u16 = 0xff01;
+const SC_ADDRESS: u16 = 0xff02;
+pub const LCDC_ADDRESS: u16 = 0xff40;
+const STAT_ADDRESS: u16 = 0xff41;
+pub const DMA_ADDRESS: u16 = 0xff46;
+
+pub struct IOMemoryBank {
+    joyp: u8,
+    data: GeneralPourposeMemoryBank<0x7f>,
+    timer: Timer,
+    pub serial: Serial,
+    pub dma_transfer_requested: bool,
+    pub stat_written: bool,
+    /// Decodes the SGB command packets sent through JOYP, in SGB mode only
+    pub sgb_packets: Option<PacketReceiver>,
+}
+
+impl IOMemoryBank {
+    pub fn new() -> Self {
+        let mut data = GeneralPourposeMemoryBank::new(0xFF01);
+        // The boot ROM hands over with the LCD turned on
+        data.write(LCDC_ADDRESS, 0x91);
+
+        Self {
+            joyp: 0xff,
+            data,
+            timer: Timer::new(),
+            serial: Serial::new(),
+            dma_transfer_requested: false,
+            stat_written: false,
+            sgb_packets: None,
+        }
+    }
+}
+
+impl IOMemoryBank {
+    /// Advances the timer by one M-cycle, returns `true` when it requests an interrupt.
+    pub fn timer_step(&mut self) -> bool {
+        self.timer.tick()
+    }
+
+    /// Updates STAT as the PPU sees it, bypassing the CPU write rules.
+    pub fn set_lcd_stat(&mut self, value: u8) {
+        self.data.write(STAT_ADDRESS, value);
+    }
+
+    /// Updates the button lines of JOYP as seen by the CPU, which can only
+    /// write the line selection bits.
+    pub fn set_joypad_inputs(&mut self, value: u8) {
+        self.joyp = (self.joyp & 0xf0) | (value & 0x0f);
+    }
+
+    /// Advances the serial port by one M-cycle, returns `true` when a
+    /// transfer completes.
+    pub fn serial_step(&mut self) -> bool {
+        self.serial.step()
+    }
+}
+
+impl MemoryBank for IOMemoryBank {
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            JOYP_ADDRESS => match self
+                .sgb_packets
+                .as_ref()
+                .and_then(PacketReceiver::joypad_id)
+            {
+                Some(id) => (self.joyp & 0xf0) | id,
+                None => self.joyp,
+            },
+            SB_ADDRESS => self.serial.read_sb(),
+            SC_ADDRESS => self.serial.read_sc(),
+            DIV_ADDRESS..=TAC_ADDRESS => self.timer.read(address),
+            _ => self.data.read(address),
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            JOYP_ADDRESS => {
+                self.joyp = handle_joyp_write(self.joyp, value);
+                if let Some(sgb_packets) = &mut self.sgb_packets {
+                    sgb_packets.write(value);
+                }
+            }
+            SB_ADDRESS => self.serial.write_sb(value),
+            SC_ADDRESS => self.serial.write_sc(value),
+            DIV_ADDRESS..=TAC_ADDRESS => self.timer.write(address, value),
+            STAT_ADDRESS => {
+                // Mode and LY=LYC bits are read only
+                let stat = self.data.read(STAT_ADDRESS);
+                self.data
+                    .write(STAT_ADDRESS, (stat & 0x07) | (value & 0x78));
+                self.stat_written = true;
+            }
+            DMA_ADDRESS => {
+                self.data.write(DMA_ADDRESS, value);
+                self.dma_transfer_requested = true;
+            }
+            _ => self.data.write(address, value),
+        }
+    }
+}
+
+/// Only the line selection bits are writable, the buttons are set by
+/// [`IOMemoryBank::set_joypad_inputs`].
+fn handle_joyp_write(old_value: u8, value: u8) -> u8 {
+    (old_value & 0b0000_1111) | (value & 0b0011_0000) | 0xc0
+}
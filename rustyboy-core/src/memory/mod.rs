@@ -0,0 +1,542 @@
+use crate::cartridge::Cartridge;
+use crate::cpu::interrupts::{Interrupt, Interrupts};
+use crate::model::{Model, Speed};
+use crate::ppu::palette::ColorPalettes;
+use crate::ppu::tiles::TileCache;
+use crate::ppu::PpuMode;
+use crate::sgb::packets::PacketReceiver;
+
+use self::dma::OamDma;
+use self::hdma::VramDma;
+use self::io::{IOMemoryBank, DMA_ADDRESS, LCDC_ADDRESS};
+use self::timer::DIV_ADDRESS;
+
+mod dma;
+mod hdma;
+mod io;
+mod timer;
+
+const TILE_MAPS_ADDRESS: u16 = 0x9800;
+
+const KEY1_ADDRESS: u16 = 0xff4d;
+const VBK_ADDRESS: u16 = 0xff4f;
+const HDMA1_ADDRESS: u16 = 0xff51;
+const HDMA2_ADDRESS: u16 = 0xff52;
+const HDMA3_ADDRESS: u16 = 0xff53;
+const HDMA4_ADDRESS: u16 = 0xff54;
+const HDMA5_ADDRESS: u16 = 0xff55;
+/// Time taken to copy a VRAM DMA block, 8 M-cycles at normal speed
+const VRAM_DMA_BLOCK_DOTS: u16 = 32;
+const BCPS_ADDRESS: u16 = 0xff68;
+const BCPD_ADDRESS: u16 = 0xff69;
+const OCPS_ADDRESS: u16 = 0xff6a;
+const OCPD_ADDRESS: u16 = 0xff6b;
+const SVBK_ADDRESS: u16 = 0xff70;
+
+trait MemoryBank {
+    fn read(&self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+}
+
+pub struct GeneralPourposeMemoryBank<const C: usize> {
+    data: [u8; C],
+    offset: usize,
+}
+
+impl<const C: usize> std::fmt::Debug for GeneralPourposeMemoryBank<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let rows = self.data.chunks(16);
+        for (i, row) in rows.enumerate() {
+            let row_str = row
+                .iter()
+                .map(|&x| format!("{:02x}", x))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "{:04X} {}", self.offset + i * 0x10, row_str)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<const C: usize> GeneralPourposeMemoryBank<C> {
+    fn new(offset: usize) -> Self {
+        GeneralPourposeMemoryBank {
+            data: [0; C],
+            offset,
+        }
+    }
+}
+
+impl<const C: usize> MemoryBank for GeneralPourposeMemoryBank<C> {
+    fn read(&self, address: u16) -> u8 {
+        self.data[address as usize - self.offset]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.data[address as usize - self.offset] = value;
+    }
+}
+
+impl MemoryBank for u8 {
+    fn read(&self, _address: u16) -> u8 {
+        *self
+    }
+
+    fn write(&mut self, _address: u16, value: u8) {
+        *self = value;
+    }
+}
+
+pub struct Memory {
+    pub cartridge: Cartridge,
+    pub model: Model,
+    pub speed: Speed,
+    speed_switch_armed: bool,
+    vram: [GeneralPourposeMemoryBank<0x2000>; 2],
+    vram_bank: usize,
+    pub work_ram: GeneralPourposeMemoryBank<0x1000>,
+    /// Switchable WRAM banks 1-7, DMG only has bank 1
+    work_ram_1_n: [GeneralPourposeMemoryBank<0x1000>; 7],
+    work_ram_bank: usize,
+    oam: GeneralPourposeMemoryBank<0x100>,
+    pub io_registers: IOMemoryBank,
+    hram: GeneralPourposeMemoryBank<0x7f>,
+    interrupt_enable: u8,
+    /// PPU mode used to lock the CPU out of VRAM and OAM
+    pub ppu_mode: PpuMode,
+    pub access_restrictions: bool,
+    dma: OamDma,
+    vram_dma: VramDma,
+    /// M-cycles run by the CPU since the last call to `take_instruction_cycles`
+    instruction_cycles: u16,
+    tile_caches: [TileCache; 2],
+    pub bg_palettes: ColorPalettes,
+    pub obj_palettes: ColorPalettes,
+}
+
+impl Memory {
+    pub fn new(cartridge: Cartridge, model: Model) -> Memory {
+        let mut io_registers = IOMemoryBank::new();
+        if model.is_sgb() {
+            io_registers.sgb_packets = Some(PacketReceiver::new());
+        }
+
+        Memory {
+            cartridge,
+            model,
+            speed: Speed::Normal,
+            speed_switch_armed: false,
+            vram: std::array::from_fn(|_| GeneralPourposeMemoryBank::new(0x8000)),
+            vram_bank: 0,
+            work_ram: GeneralPourposeMemoryBank::new(0xC000),
+            work_ram_1_n: std::array::from_fn(|_| GeneralPourposeMemoryBank::new(0xd000)),
+            work_ram_bank: 1,
+            oam: GeneralPourposeMemoryBank::new(0xFE00),
+            io_registers,
+            hram: GeneralPourposeMemoryBank::new(0xFF80),
+            interrupt_enable: 0,
+            ppu_mode: PpuMode::HBlank,
+            access_restrictions: true,
+            dma: OamDma::new(),
+            vram_dma: VramDma::new(),
+            instruction_cycles: 0,
+            tile_caches: [TileCache::new(), TileCache::new()],
+            bg_palettes: ColorPalettes::new(),
+            obj_palettes: ColorPalettes::new(),
+        }
+    }
+
+    /// While OAM DMA is running the CPU can't access OAM, nor the bus the
+    /// transfer reads from: VRAM or the external bus (cartridge and WRAM).
+    fn is_locked_by_dma(&self, address: u16) -> bool {
+        let source = match self.dma.source() {
+            Some(source) => source,
+            None => return false,
+        };
+
+        match address {
+            0xfe00..=0xfeff => true,
+            0xff00..=0xffff => false,
+            _ => is_vram(address) == is_vram(source),
+        }
+    }
+
+    /// VRAM can't be accessed by the CPU during pixel transfer.
+    fn is_vram_accessible(&self) -> bool {
+        !self.access_restrictions || !matches!(self.ppu_mode, PpuMode::PixelTransfer)
+    }
+
+    /// OAM can't be accessed by the CPU during OAM search and pixel transfer.
+    fn is_oam_accessible(&self) -> bool {
+        !self.access_restrictions
+            || !matches!(self.ppu_mode, PpuMode::OamSearch | PpuMode::PixelTransfer)
+    }
+
+    /// CGB palette data can't be accessed by the CPU during pixel transfer.
+    fn is_palette_accessible(&self) -> bool {
+        self.is_vram_accessible()
+    }
+
+    /// Reads OAM on behalf of the PPU, which is never locked out of it.
+    pub fn ppu_read_oam(&self, address: u16) -> u8 {
+        self.oam.read(address)
+    }
+
+    /// Reads a VRAM bank on behalf of the PPU, which is never locked out of it.
+    pub fn ppu_read_vram(&self, bank: u8, address: u16) -> u8 {
+        self.vram[bank as usize & 0x01].read(address)
+    }
+
+    /// Decoded tile row stored at `address` in a VRAM bank, `address` must be
+    /// in the tile data area.
+    pub fn tile_row(&self, bank: u8, address: u16) -> [u8; 8] {
+        self.tile_caches[bank as usize & 0x01].row(address)
+    }
+
+    fn write_vram(&mut self, address: u16, value: u8) {
+        let vram = &mut self.vram[self.vram_bank];
+        vram.write(address, value);
+
+        if address < TILE_MAPS_ADDRESS {
+            let row_address = address & !0x01;
+            let low = vram.read(row_address);
+            let high = vram.read(row_address + 1);
+            self.tile_caches[self.vram_bank].update(row_address, low, high);
+        }
+    }
+
+    /// Reads the CGB only registers, `None` for the other addresses or in DMG mode.
+    fn read_cgb_register(&self, address: u16) -> Option<u8> {
+        if !self.model.is_cgb() {
+            return None;
+        }
+
+        let value = match address {
+            KEY1_ADDRESS => {
+                let double_speed = (self.speed == Speed::Double) as u8;
+                0x7e | double_speed << 7 | self.speed_switch_armed as u8
+            }
+            VBK_ADDRESS => 0xfe | self.vram_bank as u8,
+            HDMA1_ADDRESS..=HDMA4_ADDRESS => 0xff,
+            HDMA5_ADDRESS => self.vram_dma.read_control(),
+            SVBK_ADDRESS => 0xf8 | self.work_ram_bank as u8,
+            BCPS_ADDRESS => self.bg_palettes.read_specification(),
+            OCPS_ADDRESS => self.obj_palettes.read_specification(),
+            BCPD_ADDRESS if self.is_palette_accessible() => self.bg_palettes.read_data(),
+            OCPD_ADDRESS if self.is_palette_accessible() => self.obj_palettes.read_data(),
+            BCPD_ADDRESS | OCPD_ADDRESS => 0xff,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Writes the CGB only registers, returns `false` for the other addresses
+    /// or in DMG mode.
+    fn write_cgb_register(&mut self, address: u16, value: u8) -> bool {
+        if !self.model.is_cgb() {
+            return false;
+        }
+
+        match address {
+            KEY1_ADDRESS => self.speed_switch_armed = value & 0x01 != 0,
+            VBK_ADDRESS => self.vram_bank = value as usize & 0x01,
+            HDMA1_ADDRESS => self.vram_dma.write_source_high(value),
+            HDMA2_ADDRESS => self.vram_dma.write_source_low(value),
+            HDMA3_ADDRESS => self.vram_dma.write_destination_high(value),
+            HDMA4_ADDRESS => self.vram_dma.write_destination_low(value),
+            HDMA5_ADDRESS => {
+                let lcd_enabled = self.io_registers.read(LCDC_ADDRESS) & 0x80 != 0;
+                self.vram_dma.write_control(value, lcd_enabled)
+            }
+            SVBK_ADDRESS => self.work_ram_bank = (value as usize & 0x07).max(1),
+            BCPS_ADDRESS => self.bg_palettes.write_specification(value),
+            OCPS_ADDRESS => self.obj_palettes.write_specification(value),
+            BCPD_ADDRESS => {
+                if self.is_palette_accessible() {
+                    self.bg_palettes.write_data(value)
+                }
+            }
+            OCPD_ADDRESS => {
+                if self.is_palette_accessible() {
+                    self.obj_palettes.write_data(value)
+                }
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Switches the CPU speed if it was requested through KEY1, called by STOP.
+    ///
+    /// Returns `false` when no switch was armed.
+    pub fn switch_speed(&mut self) -> bool {
+        if !self.model.is_cgb() || !self.speed_switch_armed {
+            return false;
+        }
+
+        self.speed = match self.speed {
+            Speed::Normal => Speed::Double,
+            Speed::Double => Speed::Normal,
+        };
+        self.speed_switch_armed = false;
+        // The divider is reset by STOP
+        self.io_registers.write(DIV_ADDRESS, 0);
+
+        true
+    }
+
+    /// Notifies the start of HBlank, an HBlank VRAM DMA can copy its next block.
+    pub fn enter_hblank(&mut self) {
+        self.vram_dma.hblank();
+    }
+
+    /// Copies the VRAM DMA blocks that are due.
+    ///
+    /// Returns the number of M-cycles the CPU is stalled for.
+    pub fn vram_dma_step(&mut self) -> u16 {
+        let mut stalled_cycles = 0;
+
+        while let Some((source, destination)) = self.vram_dma.next_block() {
+            for offset in 0..0x10 {
+                let value = self.bus_read(source.wrapping_add(offset));
+                self.write_vram(destination + offset, value);
+            }
+            stalled_cycles += VRAM_DMA_BLOCK_DOTS / self.speed.dots_per_cycle();
+        }
+
+        stalled_cycles
+    }
+
+    /// Runs an M-cycle of the hardware clocked by the CPU: OAM DMA, the timer
+    /// and the serial port.
+    pub fn cpu_cycle(&mut self) {
+        self.dma_step();
+
+        if self.io_registers.timer_step() {
+            Interrupts::dispatch_interrupt(Interrupt::Timer, self);
+        }
+        if self.io_registers.serial_step() {
+            Interrupts::dispatch_interrupt(Interrupt::Serial, self);
+        }
+    }
+
+    /// Runs an M-cycle of the instruction being executed. The CPU accesses
+    /// memory at the end of the cycle, once the rest of the hardware caught up.
+    pub fn cpu_tick(&mut self) {
+        self.cpu_cycle();
+        self.instruction_cycles += 1;
+    }
+
+    /// M-cycles already run by the instruction being executed, the counter is reset.
+    pub fn take_instruction_cycles(&mut self) -> u16 {
+        std::mem::take(&mut self.instruction_cycles)
+    }
+
+    /// Reads a byte for the CPU, taking an M-cycle.
+    pub fn cpu_read(&mut self, address: u16) -> u8 {
+        self.cpu_tick();
+        self.read(address)
+    }
+
+    pub fn cpu_read_signed(&mut self, address: u16) -> i8 {
+        self.cpu_read(address) as i8
+    }
+
+    /// Reads a little-endian word for the CPU, taking 2 M-cycles.
+    pub fn cpu_read16(&mut self, address: u16) -> u16 {
+        let low = self.cpu_read(address);
+        let high = self.cpu_read(address.wrapping_add(1));
+        u16::from_le_bytes([low, high])
+    }
+
+    /// Writes a byte for the CPU, taking an M-cycle.
+    pub fn cpu_write(&mut self, address: u16, value: u8) {
+        self.cpu_tick();
+        self.write(address, value);
+    }
+
+    /// Writes a little-endian word for the CPU, taking 2 M-cycles.
+    pub fn cpu_write16(&mut self, address: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.cpu_write(address, low);
+        self.cpu_write(address.wrapping_add(1), high);
+    }
+
+    /// Advances OAM DMA by one M-cycle.
+    fn dma_step(&mut self) {
+        if let Some((source, destination)) = self.dma.step() {
+            // Sources above WRAM read its echo
+            let source = if source >= 0xe000 {
+                source - 0x2000
+            } else {
+                source
+            };
+            let value = match source {
+                0x8000..=0x9fff => self.vram[self.vram_bank].read(source),
+                _ => self.bus_read(source),
+            };
+            self.oam.write(destination, value);
+        }
+    }
+
+    #[inline(always)]
+    pub fn read(&self, address: u16) -> u8 {
+        if self.is_locked_by_dma(address) {
+            return 0xff;
+        }
+
+        self.bus_read(address)
+    }
+
+    /// Reads a byte for a debugger, ignoring the OAM DMA lock.
+    pub fn debug_read(&self, address: u16) -> u8 {
+        self.bus_read(address)
+    }
+
+    #[inline(always)]
+    fn bus_read(&self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x7fff => self.cartridge.read(address),
+            0x8000..=0x9fff if self.is_vram_accessible() => self.vram[self.vram_bank].read(address),
+            0x8000..=0x9fff => 0xff,
+            0xa000..=0xbfff => self.cartridge.read(address),
+            0xc000..=0xcfff => self.work_ram.read(address),
+            0xd000..=0xdfff => self.work_ram_1_n[self.work_ram_bank - 1].read(address),
+            0xe000..=0xfdff => {
+                let new_addr = address - 0x2000;
+                self.bus_read(new_addr)
+            }
+            0xfe00..=0xfeff if self.is_oam_accessible() => self.oam.read(address),
+            0xfe00..=0xfeff => 0xff,
+            0xff00..=0xff7f => self
+                .read_cgb_register(address)
+                .unwrap_or_else(|| self.io_registers.read(address)),
+            0xff80..=0xfffe => self.hram.read(address),
+            0xffff => self.interrupt_enable.read(address),
+        }
+    }
+
+    pub fn read_bytes<const C: usize>(&self, start_address: u16) -> [u8; C] {
+        let mut bytes = [0; C];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.read(start_address + i as u16);
+        }
+        bytes
+    }
+
+    #[inline(always)]
+    pub fn write(&mut self, address: u16, value: u8) {
+        if self.is_locked_by_dma(address) {
+            return;
+        }
+
+        match address {
+            0x0000..=0x7fff => self.cartridge.write(address, value),
+            0x8000..=0x9fff => {
+                if self.is_vram_accessible() {
+                    self.write_vram(address, value)
+                }
+            }
+            0xa000..=0xbfff => self.cartridge.write(address, value),
+            0xc000..=0xcfff => self.work_ram.write(address, value),
+            0xd000..=0xdfff => self.work_ram_1_n[self.work_ram_bank - 1].write(address, value),
+            0xe000..=0xfdff => self.write(address - 0x2000, value),
+            0xfe00..=0xfeff => {
+                if self.is_oam_accessible() {
+                    self.oam.write(address, value)
+                }
+            }
+            0xff00..=0xff7f => {
+                if !self.write_cgb_register(address, value) {
+                    self.io_registers.write(address, value)
+                }
+            }
+            0xff80..=0xfffe => self.hram.write(address, value),
+            0xffff => self.interrupt_enable.write(address, value),
+        }
+
+        if self.io_registers.dma_transfer_requested {
+            self.io_registers.dma_transfer_requested = false;
+            self.dma.request(self.io_registers.read(DMA_ADDRESS));
+        }
+    }
+}
+
+fn is_vram(address: u16) -> bool {
+    (0x8000..=0x9fff).contains(&address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory with OAM DMA copying from `source`, after its first byte.
+    fn memory_during_dma(source: u8) -> Memory {
+        let mut memory = Memory::new(Cartridge::from_bytes(vec![0; 0x8000]).unwrap(), Model::Dmg);
+        memory.write(0xc000, 0x12);
+        memory.write(0x8000, 0x34);
+        memory.write(0xff80, 0x56);
+
+        memory.write(DMA_ADDRESS, source);
+        memory.dma_step();
+        memory.dma_step();
+        memory
+    }
+
+    #[test]
+    fn dma_from_the_external_bus_leaves_vram_accessible() {
+        let memory = memory_during_dma(0xc0);
+
+        assert_eq!(memory.read(0xc000), 0xff);
+        assert_eq!(memory.read(0x8000), 0x34);
+        assert_eq!(memory.read(0xfe00), 0xff);
+        assert_eq!(memory.read(0xff80), 0x56);
+    }
+
+    #[test]
+    fn dma_from_vram_leaves_the_external_bus_accessible() {
+        let memory = memory_during_dma(0x80);
+
+        assert_eq!(memory.read(0xc000), 0x12);
+        assert_eq!(memory.read(0x8000), 0xff);
+        assert_eq!(memory.read(0xfe00), 0xff);
+    }
+
+    /// Starts an HBlank VRAM DMA of one block from 0xc000 to 0x8000, returns
+    /// the first byte of VRAM after the copies due.
+    fn hblank_vram_dma(lcd_control: u8) -> u8 {
+        let mut memory = Memory::new(Cartridge::from_bytes(vec![0; 0x8000]).unwrap(), Model::Cgb);
+        memory.write(0xc000, 0x12);
+        memory.write(LCDC_ADDRESS, lcd_control);
+
+        memory.write(HDMA1_ADDRESS, 0xc0);
+        memory.write(HDMA2_ADDRESS, 0x00);
+        memory.write(HDMA3_ADDRESS, 0x00);
+        memory.write(HDMA4_ADDRESS, 0x00);
+        memory.write(HDMA5_ADDRESS, 0x80);
+        memory.vram_dma_step();
+
+        memory.read(0x8000)
+    }
+
+    #[test]
+    fn hblank_vram_dma_waits_for_hblank_while_the_lcd_is_on() {
+        assert_eq!(hblank_vram_dma(0x91), 0);
+    }
+
+    #[test]
+    fn hblank_vram_dma_copies_a_block_right_away_while_the_lcd_is_off() {
+        assert_eq!(hblank_vram_dma(0x11), 0x12);
+    }
+
+    #[test]
+    fn debug_reads_ignore_the_dma_lock() {
+        let memory = memory_during_dma(0xc0);
+
+        assert_eq!(memory.debug_read(0xc000), 0x12);
+        assert_eq!(memory.debug_read(0xfe00), 0x12);
+    }
+}
@@ -0,0 +1,267 @@
+pub const DIV_ADDRESS: u16 = 0xff04;
+pub const TIMA_ADDRESS: u16 = 0xff05;
+pub const TMA_ADDRESS: u16 = 0xff06;
+pub const TAC_ADDRESS: u16 = 0xff07;
+
+/// System counter value when the DMG boot ROM hands over, DIV reads 0xab.
+const BOOT_COUNTER: u16 = 0xabcc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimaState {
+    Running,
+    /// TIMA overflowed during the last M-cycle and reads 0, writing it
+    /// cancels the reload
+    Overflow,
+    /// TIMA was reloaded from TMA during the last M-cycle, writes to TIMA
+    /// are ignored and writes to TMA also go to TIMA
+    Reloading,
+}
+
+/// Timer built on the 16-bit system counter, DIV being its upper byte.
+///
+/// TIMA increments on the falling edge of a counter bit, selected by TAC,
+/// ANDed with the timer enable bit. Writing DIV or TAC can then increment
+/// TIMA as well when they make that signal fall.
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    state: TimaState,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            counter: BOOT_COUNTER,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            state: TimaState::Running,
+        }
+    }
+
+    /// Advances the timer by one M-cycle.
+    ///
+    /// Returns `true` when the timer interrupt is requested.
+    pub fn tick(&mut self) -> bool {
+        let interrupt = match self.state {
+            TimaState::Overflow => {
+                self.tima = self.tma;
+                self.state = TimaState::Reloading;
+                true
+            }
+            TimaState::Reloading => {
+                self.state = TimaState::Running;
+                false
+            }
+            TimaState::Running => false,
+        };
+
+        self.set_counter(self.counter.wrapping_add(4));
+
+        interrupt
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV_ADDRESS => (self.counter >> 8) as u8,
+            TIMA_ADDRESS => self.tima,
+            TMA_ADDRESS => self.tma,
+            TAC_ADDRESS => 0xf8 | self.tac,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            DIV_ADDRESS => self.set_counter(0),
+            TIMA_ADDRESS => match self.state {
+                TimaState::Reloading => {}
+                _ => {
+                    self.tima = value;
+                    self.state = TimaState::Running;
+                }
+            },
+            TMA_ADDRESS => {
+                self.tma = value;
+                if self.state == TimaState::Reloading {
+                    self.tima = value;
+                }
+            }
+            TAC_ADDRESS => {
+                let signal = self.signal();
+                self.tac = value & 0x07;
+                if signal && !self.signal() {
+                    self.increment_tima();
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_counter(&mut self, value: u16) {
+        let signal = self.signal();
+        self.counter = value;
+        if signal && !self.signal() {
+            self.increment_tima();
+        }
+    }
+
+    /// Counter bit selected by TAC, ANDed with the timer enable bit.
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            0b11 => 7,
+            _ => unreachable!(),
+        };
+
+        self.tac & 0x04 != 0 && (self.counter >> bit) & 0x01 != 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflow {
+            self.state = TimaState::Overflow;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Timer enabled at 262144 Hz (bit 3 of the counter) from a reset counter.
+    fn enabled_timer() -> Timer {
+        let mut timer = Timer::new();
+        timer.write(DIV_ADDRESS, 0);
+        timer.write(TAC_ADDRESS, 0x05);
+        timer
+    }
+
+    fn tick(timer: &mut Timer, cycles: u32) -> bool {
+        let mut interrupt = false;
+        for _ in 0..cycles {
+            interrupt |= timer.tick();
+        }
+        interrupt
+    }
+
+    /// Timer whose TIMA overflowed during the last M-cycle, TMA being 0x23.
+    fn overflowed_timer() -> Timer {
+        let mut timer = enabled_timer();
+        timer.write(TMA_ADDRESS, 0x23);
+        timer.write(TIMA_ADDRESS, 0xff);
+        assert!(!tick(&mut timer, 4));
+        timer
+    }
+
+    #[test]
+    fn div_is_the_upper_byte_of_the_counter() {
+        let mut timer = Timer::new();
+        assert_eq!(timer.read(DIV_ADDRESS), 0xab);
+
+        timer.write(DIV_ADDRESS, 0x12);
+        assert_eq!(timer.read(DIV_ADDRESS), 0);
+        tick(&mut timer, 64);
+        assert_eq!(timer.read(DIV_ADDRESS), 1);
+    }
+
+    #[test]
+    fn tima_increments_on_the_falling_edge_of_the_selected_bit() {
+        let mut timer = enabled_timer();
+
+        tick(&mut timer, 3);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0);
+        tick(&mut timer, 1);
+        assert_eq!(timer.read(TIMA_ADDRESS), 1);
+        tick(&mut timer, 4 * 9);
+        assert_eq!(timer.read(TIMA_ADDRESS), 10);
+    }
+
+    #[test]
+    fn tima_reads_0_for_one_cycle_before_the_reload() {
+        let mut timer = overflowed_timer();
+        assert_eq!(timer.read(TIMA_ADDRESS), 0);
+
+        assert!(timer.tick());
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x23);
+    }
+
+    #[test]
+    fn writing_tima_during_the_overflow_cycle_cancels_the_reload() {
+        let mut timer = overflowed_timer();
+        timer.write(TIMA_ADDRESS, 0x50);
+
+        assert!(!timer.tick());
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x50);
+    }
+
+    #[test]
+    fn writing_tima_during_the_reload_cycle_is_ignored() {
+        let mut timer = overflowed_timer();
+        timer.tick();
+        timer.write(TIMA_ADDRESS, 0x50);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x23);
+
+        timer.tick();
+        timer.write(TIMA_ADDRESS, 0x50);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x50);
+    }
+
+    #[test]
+    fn writing_tma_during_the_reload_cycle_also_writes_tima() {
+        let mut timer = overflowed_timer();
+        timer.tick();
+        timer.write(TMA_ADDRESS, 0x77);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x77);
+
+        timer.tick();
+        timer.write(TMA_ADDRESS, 0x11);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0x77);
+    }
+
+    #[test]
+    fn writing_div_while_the_selected_bit_is_set_increments_tima() {
+        let mut timer = enabled_timer();
+        tick(&mut timer, 1);
+        timer.write(DIV_ADDRESS, 0);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0);
+
+        tick(&mut timer, 2);
+        timer.write(DIV_ADDRESS, 0);
+        assert_eq!(timer.read(TIMA_ADDRESS), 1);
+    }
+
+    #[test]
+    fn disabling_the_timer_while_the_selected_bit_is_set_increments_tima() {
+        let mut timer = enabled_timer();
+        tick(&mut timer, 2);
+        timer.write(TAC_ADDRESS, 0x01);
+        assert_eq!(timer.read(TIMA_ADDRESS), 1);
+    }
+
+    #[test]
+    fn selecting_a_cleared_bit_while_the_selected_bit_is_set_increments_tima() {
+        let mut timer = enabled_timer();
+        tick(&mut timer, 2);
+        // Bit 5 is still clear after 8 cycles
+        timer.write(TAC_ADDRESS, 0x06);
+        assert_eq!(timer.read(TIMA_ADDRESS), 1);
+
+        let mut timer = enabled_timer();
+        tick(&mut timer, 2);
+        timer.write(TAC_ADDRESS, 0x05);
+        assert_eq!(timer.read(TIMA_ADDRESS), 0);
+    }
+
+    #[test]
+    fn tac_reads_its_unused_bits_set() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDRESS, 0x05);
+        assert_eq!(timer.read(TAC_ADDRESS), 0xfd);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::cartridge::Cartridge;
+
+/// Console model being emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    Dmg,
+    Sgb,
+    Cgb,
+}
+
+impl Model {
+    /// CGB for cartridges flagged as CGB compatible or CGB only, SGB for
+    /// cartridges using the SGB functions, DMG otherwise.
+    pub fn for_cartridge(cartridge: &Cartridge) -> Model {
+        if cartridge.header.cgb_support {
+            Model::Cgb
+        } else if cartridge.header.sgb_support {
+            Model::Sgb
+        } else {
+            Model::Dmg
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        *self == Model::Cgb
+    }
+
+    pub fn is_sgb(&self) -> bool {
+        *self == Model::Sgb
+    }
+}
+
+/// CPU clock speed, CGB can switch to double speed with KEY1 and STOP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    Normal,
+    Double,
+}
+
+impl Speed {
+    /// PPU dots elapsed during one CPU M-cycle. The PPU always runs at
+    /// 4.19 MHz while the CPU, the timer and OAM DMA follow the CPU speed.
+    pub fn dots_per_cycle(&self) -> u16 {
+        match self {
+            Speed::Normal => 4,
+            Speed::Double => 2,
+        }
+    }
+}
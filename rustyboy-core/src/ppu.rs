@@ -0,0 +1,372 @@
+pub mod fifo;
+pub mod palette;
+pub mod renderer;
+pub mod tiles;
+
+use crate::cpu::interrupts::{Interrupt, Interrupts};
+use crate::lcd::{LcdControl, LcdStat};
+use crate::memory::Memory;
+
+use self::fifo::PixelPipeline;
+use self::palette::Color;
+
+const LCD_CONTROL_ADDRESS: u16 = 0xff40;
+const LCD_STAT_ADDRESS: u16 = 0xff41;
+const LY_ADDRESS: u16 = 0xff44;
+const LYC_ADDRESS: u16 = 0xff45;
+
+const OAM_SEARCH_DOTS: u16 = 80;
+const DOTS_PER_LINE: u16 = 456;
+const VISIBLE_LINES: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+const DOTS_PER_FRAME: u32 = DOTS_PER_LINE as u32 * LINES_PER_FRAME as u32;
+
+#[derive(Clone, Copy)]
+pub enum PpuMode {
+    HBlank,
+    VBlank,
+    OamSearch,
+    PixelTransfer,
+}
+
+pub struct Ppu {
+    pub mode: PpuMode,
+    pub scanline: u8,
+    pub dots: u16,
+    pub buffer: [Color; 160 * 144],
+    /// The last frame was drawn by the LCD, not blanked because it was off
+    pub frame_rendered: bool,
+    pipeline: PixelPipeline,
+    /// Window row being drawn, only advances on lines where the window was visible
+    window_line: u8,
+    /// Set once LY matched WY during the current frame
+    window_y_triggered: bool,
+    /// WX was 166 at the end of the previous line
+    window_wrap: bool,
+    lcd_enabled: bool,
+    stat_line: bool,
+    /// Dots elapsed since the LCD was turned off, used to keep delivering blank frames
+    lcd_off_dots: u32,
+    /// The first frame after the LCD is turned on is not shown
+    first_frame: bool,
+    frames: u32,
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu {
+            mode: PpuMode::HBlank,
+            scanline: 0,
+            dots: 0,
+            buffer: [Color::White; 160 * 144],
+            frame_rendered: false,
+            pipeline: PixelPipeline::new(),
+            window_line: 0,
+            window_y_triggered: false,
+            window_wrap: false,
+            lcd_enabled: true,
+            stat_line: false,
+            lcd_off_dots: 0,
+            first_frame: false,
+            frames: 0,
+        }
+    }
+
+    /// Step the PPU.
+    ///
+    /// Returns `true` if the Frame buffer is ready to be read.
+    /// Returns `false` if the PPU is still in the middle of a frame.
+    pub fn step(&mut self, memory_bus: &mut Memory) -> bool {
+        let lcd_control = LcdControl::from(memory_bus.read(LCD_CONTROL_ADDRESS));
+        if !lcd_control.enabled {
+            return self.lcd_off_step(memory_bus);
+        }
+        if !self.lcd_enabled {
+            self.turn_lcd_on();
+        }
+
+        match self.mode {
+            PpuMode::OamSearch => {
+                if self.dots == OAM_SEARCH_DOTS - 1 {
+                    self.mode = PpuMode::PixelTransfer;
+                    self.start_pixel_transfer(memory_bus);
+                }
+            }
+            PpuMode::PixelTransfer => {
+                if self.pixel_transfer_step(memory_bus, &lcd_control) {
+                    self.mode = PpuMode::HBlank;
+                    memory_bus.enter_hblank();
+                }
+            }
+            PpuMode::HBlank | PpuMode::VBlank => {}
+        }
+
+        self.dots += 1;
+        let frame_ready = if self.dots == DOTS_PER_LINE {
+            self.next_line(memory_bus)
+        } else {
+            false
+        };
+
+        let stat_written = std::mem::take(&mut memory_bus.io_registers.stat_written);
+        self.update_stat_line(memory_bus, stat_written);
+
+        frame_ready
+    }
+
+    /// All the enabled STAT sources are ORed into a single line, the interrupt
+    /// is only requested on its rising edge.
+    fn update_stat_line(&mut self, memory_bus: &mut Memory, stat_written: bool) {
+        let lcd_stat = LcdStat::from(memory_bus.read(LCD_STAT_ADDRESS));
+        let lyc_eq_ly = self.lyc_eq_ly(memory_bus);
+        let mode = self.stat_mode();
+
+        let stat_line = if stat_written && !memory_bus.model.is_cgb() {
+            // DMG bug: on a STAT write every source is enabled for one cycle
+            lyc_eq_ly || matches!(mode, PpuMode::HBlank | PpuMode::VBlank)
+        } else {
+            (lcd_stat.lyc_eq_ly_interrupt_source && lyc_eq_ly)
+                || match mode {
+                    PpuMode::HBlank => lcd_stat.mode_0_interrupt_source,
+                    PpuMode::VBlank => lcd_stat.mode_1_interrupt_source,
+                    PpuMode::OamSearch => lcd_stat.mode_2_interrupt_source,
+                    PpuMode::PixelTransfer => false,
+                }
+        };
+
+        if stat_line && !self.stat_line {
+            Interrupts::dispatch_interrupt(Interrupt::LcdStat, memory_bus);
+        }
+        self.stat_line = stat_line;
+    }
+
+    /// LY as seen by the CPU. On line 153 it already reads 0 after one M-cycle.
+    fn ly(&self) -> u8 {
+        if self.scanline == 153 && self.dots >= 4 {
+            0
+        } else {
+            self.scanline
+        }
+    }
+
+    /// The comparison is not performed during the first 4 dots of a line,
+    /// except line 0.
+    fn lyc_eq_ly(&self, memory_bus: &Memory) -> bool {
+        if self.dots < 4 && self.scanline != 0 {
+            false
+        } else {
+            self.ly() == memory_bus.read(LYC_ADDRESS)
+        }
+    }
+
+    fn stat_mode(&self) -> PpuMode {
+        match self.mode {
+            PpuMode::OamSearch if self.first_frame && self.scanline == 0 => PpuMode::HBlank,
+            mode => mode,
+        }
+    }
+
+    /// Moves to the next line, returns `true` when entering VBlank.
+    fn next_line(&mut self, memory_bus: &mut Memory) -> bool {
+        self.dots = 0;
+        self.scanline += 1;
+
+        match self.scanline {
+            VISIBLE_LINES => {
+                self.mode = PpuMode::VBlank;
+                self.frame_rendered = !self.first_frame;
+                if self.first_frame {
+                    self.first_frame = false;
+                    self.buffer = [Color::White; 160 * 144];
+                }
+                Interrupts::dispatch_interrupt(Interrupt::VBlank, memory_bus);
+                true
+            }
+            LINES_PER_FRAME => {
+                self.frames += 1;
+                self.start_frame();
+                false
+            }
+            scanline if scanline < VISIBLE_LINES => {
+                self.mode = PpuMode::OamSearch;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn start_frame(&mut self) {
+        self.scanline = 0;
+        self.dots = 0;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.window_wrap = false;
+        self.mode = PpuMode::OamSearch;
+    }
+
+    /// While the LCD is off LY stays at 0, STAT reports mode 0 and no
+    /// interrupts are raised. A blank frame is still delivered at the usual
+    /// rate so the frontends keep running.
+    fn lcd_off_step(&mut self, memory_bus: &mut Memory) -> bool {
+        // A STAT write has no effect on the line until the LCD is on again
+        memory_bus.io_registers.stat_written = false;
+
+        if self.lcd_enabled {
+            self.lcd_enabled = false;
+            self.stat_line = false;
+            self.scanline = 0;
+            self.dots = 0;
+            self.mode = PpuMode::HBlank;
+            self.lcd_off_dots = 0;
+            self.buffer = [Color::White; 160 * 144];
+        }
+
+        self.lcd_off_dots += 1;
+        if self.lcd_off_dots == DOTS_PER_FRAME {
+            self.lcd_off_dots = 0;
+            self.frame_rendered = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Turning the LCD on starts a new frame. On its first line OAM search is
+    /// reported as mode 0, and the whole frame is not displayed.
+    fn turn_lcd_on(&mut self) {
+        self.lcd_enabled = true;
+        self.first_frame = true;
+        self.start_frame();
+    }
+
+    pub fn update_memory(&mut self, memory_bus: &mut Memory) {
+        memory_bus.write(LY_ADDRESS, self.ly());
+        let lcd_stat = self.create_stat_byte(memory_bus);
+        memory_bus.io_registers.set_lcd_stat(lcd_stat);
+        memory_bus.ppu_mode = self.stat_mode();
+    }
+
+    fn create_stat_byte(&self, memory_bus: &Memory) -> u8 {
+        let mut lcd_stat = LcdStat::from(memory_bus.read(LCD_STAT_ADDRESS));
+        lcd_stat.lyc_eq_ly = self.lyc_eq_ly(memory_bus);
+        lcd_stat.mode = self.stat_mode();
+
+        u8::from(lcd_stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+    use crate::cpu::interrupts::INTERRUPT_FLAG_ADDRESS;
+    use crate::model::Model;
+
+    fn memory_bus(model: Model) -> Memory {
+        Memory::new(Cartridge::from_bytes(vec![0; 0x8000]).unwrap(), model)
+    }
+
+    fn dots_until_frame(ppu: &mut Ppu, memory_bus: &mut Memory) -> u32 {
+        let mut dots = 1;
+        while !ppu.step(memory_bus) {
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn frame_lasts_70224_dots() {
+        let mut memory_bus = memory_bus(Model::Dmg);
+        let mut ppu = Ppu::new();
+
+        dots_until_frame(&mut ppu, &mut memory_bus);
+        for _ in 0..3 {
+            assert_eq!(dots_until_frame(&mut ppu, &mut memory_bus), 70224);
+        }
+    }
+
+    #[test]
+    fn lines_last_456_dots_and_vblank_starts_at_line_144() {
+        let mut memory_bus = memory_bus(Model::Dmg);
+        let mut ppu = Ppu::new();
+
+        dots_until_frame(&mut ppu, &mut memory_bus);
+        assert_eq!(ppu.scanline, 144);
+        assert!(matches!(ppu.mode, PpuMode::VBlank));
+
+        let mut visible_lines = 0;
+        let mut line_dots = 0;
+        let mut scanline = ppu.scanline;
+        loop {
+            let frame_ready = ppu.step(&mut memory_bus);
+            line_dots += 1;
+
+            if ppu.scanline != scanline {
+                assert_eq!(line_dots, 456, "line {} length", scanline);
+                line_dots = 0;
+                scanline = ppu.scanline;
+            }
+            if matches!(ppu.mode, PpuMode::PixelTransfer) && ppu.dots == 80 {
+                visible_lines += 1;
+            }
+            if frame_ready {
+                break;
+            }
+        }
+
+        assert_eq!(visible_lines, 144);
+    }
+
+    /// Writes STAT during VBlank with every source disabled, returns whether
+    /// the STAT interrupt was requested.
+    fn stat_write_in_vblank_interrupts(model: Model) -> bool {
+        let mut memory_bus = memory_bus(model);
+        let mut ppu = Ppu::new();
+
+        dots_until_frame(&mut ppu, &mut memory_bus);
+        ppu.step(&mut memory_bus);
+        memory_bus.write(INTERRUPT_FLAG_ADDRESS, 0);
+
+        memory_bus.write(LCD_STAT_ADDRESS, 0);
+        ppu.step(&mut memory_bus);
+
+        memory_bus.read(INTERRUPT_FLAG_ADDRESS) & 0x02 != 0
+    }
+
+    #[test]
+    fn stat_write_while_the_lcd_is_off_is_dropped() {
+        let mut memory_bus = memory_bus(Model::Dmg);
+        let mut ppu = Ppu::new();
+
+        memory_bus.write(LCD_CONTROL_ADDRESS, 0);
+        ppu.step(&mut memory_bus);
+        memory_bus.write(LCD_STAT_ADDRESS, 0);
+        ppu.step(&mut memory_bus);
+
+        memory_bus.write(INTERRUPT_FLAG_ADDRESS, 0);
+        memory_bus.write(LCD_CONTROL_ADDRESS, 0x80);
+        ppu.step(&mut memory_bus);
+        assert_eq!(memory_bus.read(INTERRUPT_FLAG_ADDRESS) & 0x02, 0);
+    }
+
+    #[test]
+    fn ly_reads_0_after_4_dots_on_line_153() {
+        let mut memory_bus = memory_bus(Model::Dmg);
+        let mut ppu = Ppu::new();
+
+        while ppu.scanline != 153 {
+            ppu.step(&mut memory_bus);
+        }
+        assert_eq!(ppu.ly(), 153);
+        for _ in 0..4 {
+            ppu.step(&mut memory_bus);
+        }
+        assert_eq!(ppu.ly(), 0);
+    }
+
+    #[test]
+    fn stat_write_bug_only_happens_on_dmg() {
+        assert!(stat_write_in_vblank_interrupts(Model::Dmg));
+        assert!(!stat_write_in_vblank_interrupts(Model::Cgb));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+
+use super::palette::SpritePalette;
+use super::tiles::{BgAttributes, Sprite};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BgPixel {
+    pub color: u8,
+    /// CGB only
+    pub palette: u8,
+    /// CGB only
+    pub bg_over_sprites: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpritePixel {
+    pub color: u8,
+    pub palette: SpritePalette,
+    pub bg_and_window_over: bool,
+    /// CGB only
+    pub cgb_palette: u8,
+    pub oam_index: u8,
+}
+
+impl SpritePixel {
+    fn transparent() -> SpritePixel {
+        SpritePixel {
+            color: 0,
+            palette: SpritePalette::OBP0,
+            bg_and_window_over: false,
+            cgb_palette: 0,
+            oam_index: u8::MAX,
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.color == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetcherStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+/// Background and window tile fetcher.
+///
+/// Every step but `Push` takes 2 dots, `Push` is retried every dot until the
+/// background FIFO is empty.
+#[derive(Debug)]
+pub struct Fetcher {
+    pub step: FetcherStep,
+    pub dots: u8,
+    pub tile_x: u8,
+    pub tile_index: u8,
+    pub attributes: BgAttributes,
+    pub row: [u8; 8],
+    pub window: bool,
+}
+
+impl Fetcher {
+    pub fn new() -> Fetcher {
+        Fetcher {
+            step: FetcherStep::Tile,
+            dots: 0,
+            tile_x: 0,
+            tile_index: 0,
+            attributes: BgAttributes::default(),
+            row: [0; 8],
+            window: false,
+        }
+    }
+
+    pub fn restart(&mut self, window: bool) {
+        *self = Fetcher {
+            window,
+            ..Fetcher::new()
+        };
+    }
+}
+
+/// A sprite waiting for its tile row to be fetched.
+#[derive(Debug)]
+pub struct SpriteFetch {
+    pub sprite: Sprite,
+    pub dots: u8,
+}
+
+/// State of the pixel pipeline while the PPU is in pixel transfer.
+pub struct PixelPipeline {
+    pub fetcher: Fetcher,
+    pub bg_fifo: VecDeque<BgPixel>,
+    pub sprite_fifo: VecDeque<SpritePixel>,
+    pub sprites: Vec<Sprite>,
+    pub next_sprite: usize,
+    pub sprite_fetch: Option<SpriteFetch>,
+    /// Next pixel of the line that will be pushed to the LCD
+    pub x: u8,
+    /// Pixels still to be dropped because of the fine horizontal scroll
+    pub discard: u8,
+    /// Dots spent in the first, discarded, tile fetch of the line
+    pub startup_delay: u8,
+    pub window_active: bool,
+}
+
+impl PixelPipeline {
+    pub fn new() -> PixelPipeline {
+        PixelPipeline {
+            fetcher: Fetcher::new(),
+            bg_fifo: VecDeque::with_capacity(16),
+            sprite_fifo: VecDeque::with_capacity(16),
+            sprites: Vec::with_capacity(40),
+            next_sprite: 0,
+            sprite_fetch: None,
+            x: 0,
+            discard: 0,
+            startup_delay: 0,
+            window_active: false,
+        }
+    }
+
+    pub fn push_bg_row(&mut self, row: [u8; 8], attributes: BgAttributes) {
+        self.bg_fifo.extend(row.iter().map(|&color| BgPixel {
+            color,
+            palette: attributes.palette,
+            bg_over_sprites: attributes.bg_over_sprites,
+        }));
+    }
+
+    /// Mixes a sprite row in the sprite FIFO. `skip` pixels are clipped off
+    /// the left side.
+    ///
+    /// On DMG pixels already occupied by an earlier sprite, which has a lower
+    /// X, are kept. On CGB the sprite with the lowest OAM index wins instead.
+    pub fn merge_sprite_row(&mut self, sprite: &Sprite, row: [u8; 8], skip: usize, cgb: bool) {
+        while self.sprite_fifo.len() < 8 {
+            self.sprite_fifo.push_back(SpritePixel::transparent());
+        }
+
+        for (i, &color) in row.iter().enumerate().skip(skip) {
+            let pixel = &mut self.sprite_fifo[i - skip];
+            let wins =
+                pixel.is_transparent() || (cgb && color != 0 && sprite.oam_index < pixel.oam_index);
+            if wins {
+                *pixel = SpritePixel {
+                    color,
+                    palette: sprite.sprite_flags.palette_number,
+                    bg_and_window_over: sprite.sprite_flags.bg_and_window_over,
+                    cgb_palette: sprite.sprite_flags.cgb_palette,
+                    oam_index: sprite.oam_index,
+                };
+            }
+        }
+    }
+}
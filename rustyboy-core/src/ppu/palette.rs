@@ -0,0 +1,387 @@
+use anyhow::{anyhow, Result};
+
+use crate::memory::Memory;
+
+const BG_PALETTE_ADDRESS: u16 = 0xff47;
+const OBP0_PALETTE_ADDRESS: u16 = 0xff48;
+const OBP1_PALETTE_ADDRESS: u16 = 0xff49;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Transparent,
+    White,
+    LightGray,
+    DarkGray,
+    Black,
+    /// DMG sprite shade, from 0 (white) to 3 (black), tagged with its palette so
+    /// it can be displayed with its own colors
+    Sprite(SpritePalette, u8),
+    /// CGB color, 5 bits per channel with red in the lowest bits
+    Rgb(u16),
+}
+
+impl std::convert::From<u8> for Color {
+    fn from(value: u8) -> Color {
+        match value & 0x03 {
+            0x00 => Color::White,
+            0x01 => Color::LightGray,
+            0x02 => Color::DarkGray,
+            0x03 => Color::Black,
+            _ => panic!("Invalid color"),
+        }
+    }
+}
+
+impl Color {
+    /// RGB bytes for the color, using the default gray shades.
+    pub fn to_rgb(&self) -> [u8; 3] {
+        DisplayPalette::default().rgb(*self)
+    }
+
+    /// Perceived brightness of the color, from 0 to 255.
+    pub fn luma(&self) -> u8 {
+        let [r, g, b] = self.to_rgb();
+        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+    }
+}
+
+/// Expands a 15-bit CGB color to 8 bits per channel.
+fn rgb555_to_rgb(value: u16) -> [u8; 3] {
+    let channel = |shift: u16| {
+        let value = ((value >> shift) & 0x1f) as u8;
+        (value << 3) | (value >> 2)
+    };
+    [channel(0), channel(5), channel(10)]
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Color::White => write!(f, " "),
+            Color::LightGray => write!(f, "░"),
+            Color::DarkGray => write!(f, "▒"),
+            Color::Black => write!(f, "█"),
+            Color::Transparent => write!(f, " "),
+            Color::Sprite(_, shade) => Color::from(*shade).fmt(f),
+            Color::Rgb(_) => match self.luma() {
+                0xc0..=0xff => write!(f, " "),
+                0x80..=0xbf => write!(f, "░"),
+                0x40..=0x7f => write!(f, "▒"),
+                _ => write!(f, "█"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PaletteType {
+    Background,
+    Sprite(SpritePalette),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpritePalette {
+    OBP0,
+    OBP1,
+}
+
+#[derive(Debug, Clone)]
+pub struct Palette {
+    colors: [Color; 4],
+}
+
+impl Palette {
+    pub fn from_u8(value: u8, palette_type: PaletteType) -> Palette {
+        let shade = |index: u8| {
+            let shade = (value >> (index * 2)) & 0x03;
+            match palette_type {
+                PaletteType::Background => Color::from(shade),
+                PaletteType::Sprite(_) if index == 0 => Color::Transparent,
+                PaletteType::Sprite(palette) => Color::Sprite(palette, shade),
+            }
+        };
+        let colors = [shade(0), shade(1), shade(2), shade(3)];
+
+        Palette { colors }
+    }
+
+    pub fn background(memory_bus: &Memory) -> Palette {
+        let value = memory_bus.read(BG_PALETTE_ADDRESS);
+        Palette::from_u8(value, PaletteType::Background)
+    }
+
+    pub fn obp0(memory_bus: &Memory) -> Palette {
+        let value = memory_bus.read(OBP0_PALETTE_ADDRESS);
+        Palette::from_u8(value, PaletteType::Sprite(SpritePalette::OBP0))
+    }
+
+    pub fn obp1(memory_bus: &Memory) -> Palette {
+        let value = memory_bus.read(OBP1_PALETTE_ADDRESS);
+        Palette::from_u8(value, PaletteType::Sprite(SpritePalette::OBP1))
+    }
+
+    pub fn sprite(memory_bus: &Memory, palette: SpritePalette) -> Palette {
+        match palette {
+            SpritePalette::OBP0 => Palette::obp0(memory_bus),
+            SpritePalette::OBP1 => Palette::obp1(memory_bus),
+        }
+    }
+
+    pub fn color(&self, color_index: u8) -> Color {
+        self.colors[color_index as usize & 0x03]
+    }
+}
+
+impl std::default::Default for Palette {
+    fn default() -> Self {
+        Palette {
+            colors: [
+                Color::White,
+                Color::LightGray,
+                Color::DarkGray,
+                Color::Black,
+            ],
+        }
+    }
+}
+
+/// CGB palette memory, accessed through BCPS/BCPD or OCPS/OCPD.
+///
+/// Holds 8 palettes of 4 colors, each color is a little endian 15-bit value.
+pub struct ColorPalettes {
+    data: [u8; 64],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl ColorPalettes {
+    pub fn new() -> ColorPalettes {
+        ColorPalettes {
+            data: [0xff; 64],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+
+    pub fn read_specification(&self) -> u8 {
+        ((self.auto_increment as u8) << 7) | 0x40 | self.index
+    }
+
+    pub fn write_specification(&mut self, value: u8) {
+        self.auto_increment = value & 0x80 != 0;
+        self.index = value & 0x3f;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        self.data[self.index as usize] = value;
+        if self.auto_increment {
+            self.index = (self.index + 1) & 0x3f;
+        }
+    }
+
+    pub fn color(&self, palette: u8, color_index: u8) -> Color {
+        let offset = (palette as usize & 0x07) * 8 + (color_index as usize & 0x03) * 2;
+        let value = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]);
+        Color::Rgb(value & 0x7fff)
+    }
+}
+
+/// Four RGB colors, from the lightest shade to the darkest.
+pub type Shades = [[u8; 3]; 4];
+
+const GRAYSCALE: Shades = [
+    [0xff, 0xff, 0xff],
+    [0xcc, 0xcc, 0xcc],
+    [0x77, 0x77, 0x77],
+    [0x00, 0x00, 0x00],
+];
+const GREEN: Shades = [
+    [0x9b, 0xbc, 0x0f],
+    [0x8b, 0xac, 0x0f],
+    [0x30, 0x62, 0x30],
+    [0x0f, 0x38, 0x0f],
+];
+const POCKET: Shades = [
+    [0xc4, 0xcf, 0xa1],
+    [0x8b, 0x95, 0x6d],
+    [0x4d, 0x53, 0x3c],
+    [0x1f, 0x1f, 0x1f],
+];
+const LIGHT: Shades = [
+    [0x00, 0xb5, 0x81],
+    [0x00, 0x9a, 0x71],
+    [0x00, 0x69, 0x4a],
+    [0x00, 0x4f, 0x3b],
+];
+
+/// Maps the colors of a frame to the RGB colors shown on screen.
+///
+/// DMG shades use separate colors for the background and each sprite palette,
+/// like the palettes the CGB boot ROM picks for DMG games.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayPalette {
+    pub background: Shades,
+    pub obj0: Shades,
+    pub obj1: Shades,
+    /// Approximates how CGB colors look on the CGB LCD instead of showing
+    /// the raw 15-bit values
+    pub color_correction: bool,
+}
+
+impl DisplayPalette {
+    /// Uses the same colors for the background and the sprites.
+    pub fn new(colors: Shades) -> DisplayPalette {
+        DisplayPalette {
+            background: colors,
+            obj0: colors,
+            obj1: colors,
+            color_correction: false,
+        }
+    }
+
+    /// Presets, named `grayscale`, `green` (original DMG), `pocket` and `light`.
+    pub fn preset(name: &str) -> Option<DisplayPalette> {
+        let colors = match name {
+            "grayscale" => GRAYSCALE,
+            "green" => GREEN,
+            "pocket" => POCKET,
+            "light" => LIGHT,
+            _ => return None,
+        };
+
+        Some(DisplayPalette::new(colors))
+    }
+
+    /// Preset named `value`, or else the palette config file at path `value`.
+    pub fn find(value: &str) -> Result<DisplayPalette> {
+        match DisplayPalette::preset(value) {
+            Some(palette) => Ok(palette),
+            None => DisplayPalette::load(value),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<DisplayPalette> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|error| anyhow!("Cannot read palette {}: {}", path, error))?;
+        DisplayPalette::from_config(&config)
+    }
+
+    /// Parses a palette config, made of `key = value` lines:
+    ///
+    /// ```text
+    /// # Start from a preset, grayscale by default
+    /// preset = pocket
+    /// # Four colors, from the lightest to the darkest
+    /// background = #e0f8d0 #88c070 #346856 #081820
+    /// obj0 = #ffffff #ff8484 #943a3a #000000
+    /// obj1 = #ffffff #7bff31 #0063c5 #000000
+    /// # Or `colors` to set all three at once
+    /// color_correction = true
+    /// ```
+    pub fn from_config(config: &str) -> Result<DisplayPalette> {
+        let mut palette = DisplayPalette::default();
+
+        for (number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Line {}: expected <key> = <value>", number + 1))?;
+            let value = value.trim();
+            let error = |message: &str| anyhow!("Line {}: {}", number + 1, message);
+
+            match key.trim() {
+                "preset" => {
+                    let preset = DisplayPalette::preset(value)
+                        .ok_or_else(|| error(&format!("unknown preset {}", value)))?;
+                    palette.background = preset.background;
+                    palette.obj0 = preset.obj0;
+                    palette.obj1 = preset.obj1;
+                }
+                "colors" => {
+                    let colors = parse_shades(value).map_err(|message| error(&message))?;
+                    palette.background = colors;
+                    palette.obj0 = colors;
+                    palette.obj1 = colors;
+                }
+                "background" => {
+                    palette.background = parse_shades(value).map_err(|message| error(&message))?
+                }
+                "obj0" => palette.obj0 = parse_shades(value).map_err(|message| error(&message))?,
+                "obj1" => palette.obj1 = parse_shades(value).map_err(|message| error(&message))?,
+                "color_correction" => {
+                    palette.color_correction =
+                        value.parse().map_err(|_| error("expected true or false"))?
+                }
+                key => return Err(error(&format!("unknown key {}", key))),
+            }
+        }
+
+        Ok(palette)
+    }
+
+    pub fn rgb(&self, color: Color) -> [u8; 3] {
+        match color {
+            Color::Transparent => [0x00, 0x00, 0x00],
+            Color::White => self.background[0],
+            Color::LightGray => self.background[1],
+            Color::DarkGray => self.background[2],
+            Color::Black => self.background[3],
+            Color::Sprite(SpritePalette::OBP0, shade) => self.obj0[shade as usize & 0x03],
+            Color::Sprite(SpritePalette::OBP1, shade) => self.obj1[shade as usize & 0x03],
+            Color::Rgb(value) if self.color_correction => correct_color(value),
+            Color::Rgb(value) => rgb555_to_rgb(value),
+        }
+    }
+
+    /// Converts a frame to packed RGB bytes, row by row.
+    pub fn frame_to_rgb(&self, buffer: &[Color]) -> Vec<u8> {
+        buffer.iter().flat_map(|&color| self.rgb(color)).collect()
+    }
+}
+
+impl std::default::Default for DisplayPalette {
+    fn default() -> Self {
+        DisplayPalette::new(GRAYSCALE)
+    }
+}
+
+/// Parses four colors written as `#rrggbb`, separated by spaces or commas.
+fn parse_shades(value: &str) -> std::result::Result<Shades, String> {
+    let colors = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|color| !color.is_empty())
+        .map(|color| {
+            let hex = color.trim_start_matches('#');
+            match u32::from_str_radix(hex, 16) {
+                Ok(rgb) if hex.len() == 6 => Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]),
+                _ => Err(format!("invalid color {}", color)),
+            }
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    colors
+        .try_into()
+        .map_err(|colors: Vec<_>| format!("expected 4 colors, got {}", colors.len()))
+}
+
+/// Converts a 15-bit CGB color to what the CGB LCD shows, which has less
+/// saturated colors with channels bleeding into each other.
+fn correct_color(value: u16) -> [u8; 3] {
+    let channel = |shift: u16| ((value >> shift) & 0x1f) as u32;
+    let (r, g, b) = (channel(0), channel(5), channel(10));
+
+    let correct = |value: u32| (value.min(960) >> 2) as u8;
+    [
+        correct(r * 26 + g * 4 + b * 2),
+        correct(g * 24 + b * 8),
+        correct(r * 6 + g * 4 + b * 22),
+    ]
+}
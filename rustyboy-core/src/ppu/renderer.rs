@@ -0,0 +1,333 @@
+use super::{
+    fifo::{BgPixel, FetcherStep, SpriteFetch, SpritePixel},
+    palette::{Color, Palette},
+    tiles::{BgAttributes, Sprite},
+};
+use crate::{lcd::LcdControl, memory::Memory};
+
+use super::Ppu;
+
+const LCD_CONTROL_ADDRESS: u16 = 0xff40;
+const SCY_ADDRESS: u16 = 0xff42;
+const SCX_ADDRESS: u16 = 0xff43;
+const WY_ADDRESS: u16 = 0xff4a;
+const WX_ADDRESS: u16 = 0xff4b;
+const OAM_ADDRESS: u16 = 0xfe00;
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+// The first tile fetched on every line is thrown away
+const STARTUP_DELAY: u8 = 6;
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+impl Ppu {
+    /// Resets the pixel pipeline at the beginning of pixel transfer.
+    pub fn start_pixel_transfer(&mut self, memory_bus: &Memory) {
+        self.select_sprites(memory_bus);
+
+        if self.scanline == memory_bus.read(WY_ADDRESS) {
+            self.window_y_triggered = true;
+        }
+
+        let pipeline = &mut self.pipeline;
+        pipeline.fetcher.restart(false);
+        pipeline.bg_fifo.clear();
+        pipeline.sprite_fifo.clear();
+        pipeline.next_sprite = 0;
+        pipeline.sprite_fetch = None;
+        pipeline.x = 0;
+        pipeline.discard = memory_bus.read(SCX_ADDRESS) % 8;
+        pipeline.startup_delay = STARTUP_DELAY;
+        pipeline.window_active = false;
+    }
+
+    /// Advances pixel transfer by one dot.
+    ///
+    /// Returns `true` once the whole line has been pushed to the LCD.
+    pub fn pixel_transfer_step(&mut self, memory_bus: &Memory, lcd_control: &LcdControl) -> bool {
+        if self.pipeline.startup_delay > 0 {
+            self.pipeline.startup_delay -= 1;
+            return false;
+        }
+
+        self.fetcher_step(memory_bus, lcd_control);
+
+        if self.pipeline.sprite_fetch.is_some() {
+            self.sprite_fetch_step(memory_bus, lcd_control);
+            return false;
+        }
+
+        if self.pipeline.bg_fifo.is_empty() {
+            return false;
+        }
+
+        if self.pipeline.discard > 0 {
+            self.pipeline.bg_fifo.pop_front();
+            self.pipeline.discard -= 1;
+            return false;
+        }
+
+        if !self.pipeline.window_active && self.is_window_reached(memory_bus, lcd_control) {
+            self.pipeline.window_active = true;
+            self.pipeline.bg_fifo.clear();
+            self.pipeline.fetcher.restart(true);
+            // With WX < 7 the window starts off-screen, its first columns are dropped
+            self.pipeline.discard = 7u8.saturating_sub(memory_bus.read(WX_ADDRESS));
+            return false;
+        }
+
+        if lcd_control.object_enable {
+            if let Some(&sprite) = self.pipeline.sprites.get(self.pipeline.next_sprite) {
+                if sprite.x <= self.pipeline.x + 8 {
+                    self.pipeline.sprite_fetch = Some(SpriteFetch { sprite, dots: 0 });
+                    self.pipeline.next_sprite += 1;
+                    return false;
+                }
+            }
+        }
+
+        self.push_pixel(memory_bus, lcd_control);
+
+        if self.pipeline.x == 160 {
+            self.end_line(memory_bus, lcd_control);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn end_line(&mut self, memory_bus: &Memory, lcd_control: &LcdControl) {
+        if self.pipeline.window_active {
+            self.window_line = self.window_line.wrapping_add(1);
+        }
+
+        self.window_wrap = lcd_control.window_enabled
+            && self.window_y_triggered
+            && memory_bus.read(WX_ADDRESS) == 166;
+    }
+
+    fn is_window_reached(&self, memory_bus: &Memory, lcd_control: &LcdControl) -> bool {
+        let window_x = memory_bus.read(WX_ADDRESS);
+        if !lcd_control.window_enabled || !self.window_y_triggered {
+            return false;
+        }
+
+        if window_x == 166 {
+            // WX=166 is never reached on the current line, the window spans the next one
+            false
+        } else {
+            self.pipeline.x + 7 >= window_x || (self.window_wrap && self.pipeline.x == 0)
+        }
+    }
+
+    fn fetcher_step(&mut self, memory_bus: &Memory, lcd_control: &LcdControl) {
+        let step = self.pipeline.fetcher.step;
+
+        if step == FetcherStep::Push {
+            if self.pipeline.bg_fifo.is_empty() {
+                let fetcher = &self.pipeline.fetcher;
+                let (row, attributes) = (fetcher.row, fetcher.attributes);
+                self.pipeline.push_bg_row(row, attributes);
+
+                let fetcher = &mut self.pipeline.fetcher;
+                fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+                fetcher.step = FetcherStep::Tile;
+            }
+            return;
+        }
+
+        self.pipeline.fetcher.dots += 1;
+        if self.pipeline.fetcher.dots < 2 {
+            return;
+        }
+        self.pipeline.fetcher.dots = 0;
+
+        match step {
+            FetcherStep::Tile => {
+                let address = self.tile_map_address(memory_bus, lcd_control);
+                let fetcher = &mut self.pipeline.fetcher;
+                fetcher.tile_index = memory_bus.ppu_read_vram(0, address);
+                fetcher.attributes = if memory_bus.model.is_cgb() {
+                    BgAttributes::from(memory_bus.ppu_read_vram(1, address))
+                } else {
+                    BgAttributes::default()
+                };
+                fetcher.step = FetcherStep::DataLow;
+            }
+            FetcherStep::DataLow => {
+                self.pipeline.fetcher.step = FetcherStep::DataHigh;
+            }
+            FetcherStep::DataHigh => {
+                // Both bytes are sampled here, already decoded by the tile cache
+                let address = self.tile_data_address(memory_bus, lcd_control);
+                let fetcher = &mut self.pipeline.fetcher;
+                fetcher.row = memory_bus.tile_row(fetcher.attributes.vram_bank, address);
+                if fetcher.attributes.x_flip {
+                    fetcher.row.reverse();
+                }
+                fetcher.step = FetcherStep::Push;
+            }
+            FetcherStep::Push => unreachable!(),
+        }
+    }
+
+    fn tile_map_address(&self, memory_bus: &Memory, lcd_control: &LcdControl) -> u16 {
+        let fetcher = &self.pipeline.fetcher;
+
+        if fetcher.window {
+            let tile_map_area = *lcd_control.get_window_tile_map_area().start();
+            let row = self.window_line / 8;
+            tile_map_area + 32 * row as u16 + (fetcher.tile_x & 0x1f) as u16
+        } else {
+            let tile_map_area = *lcd_control.get_background_tile_map_area().start();
+            let row = self.scanline.wrapping_add(memory_bus.read(SCY_ADDRESS)) / 8;
+            let column = (memory_bus.read(SCX_ADDRESS) / 8).wrapping_add(fetcher.tile_x) & 0x1f;
+            tile_map_area + 32 * row as u16 + column as u16
+        }
+    }
+
+    fn tile_data_address(&self, memory_bus: &Memory, lcd_control: &LcdControl) -> u16 {
+        let fetcher = &self.pipeline.fetcher;
+
+        let tile_row = if fetcher.window {
+            self.window_line % 8
+        } else {
+            self.scanline.wrapping_add(memory_bus.read(SCY_ADDRESS)) % 8
+        };
+        let tile_row = if fetcher.attributes.y_flip {
+            7 - tile_row
+        } else {
+            tile_row
+        };
+
+        lcd_control.get_background_window_tile_address(fetcher.tile_index) + tile_row as u16 * 2
+    }
+
+    fn sprite_fetch_step(&mut self, memory_bus: &Memory, lcd_control: &LcdControl) {
+        // The sprite fetch starts once the background fetcher completed its tile
+        if self.pipeline.fetcher.step != FetcherStep::Push {
+            return;
+        }
+
+        let sprite_fetch = self.pipeline.sprite_fetch.as_mut().unwrap();
+        sprite_fetch.dots += 1;
+        if sprite_fetch.dots < SPRITE_FETCH_DOTS {
+            return;
+        }
+
+        let sprite = sprite_fetch.sprite;
+        self.pipeline.sprite_fetch = None;
+
+        let height = lcd_control.object_size.get_height();
+        let address = sprite.tile_row_address(self.scanline, height);
+        let cgb = memory_bus.model.is_cgb();
+        let vram_bank = if cgb {
+            sprite.sprite_flags.vram_bank
+        } else {
+            0
+        };
+        let mut row = memory_bus.tile_row(vram_bank, address);
+        if sprite.sprite_flags.x_flip {
+            row.reverse();
+        }
+        // Columns left of the screen edge, or of a sprite fetched late, are clipped
+        let skip = (self.pipeline.x + 8 - sprite.x) as usize;
+        self.pipeline.merge_sprite_row(&sprite, row, skip, cgb);
+    }
+
+    fn push_pixel(&mut self, memory_bus: &Memory, lcd_control: &LcdControl) {
+        let bg_pixel = self.pipeline.bg_fifo.pop_front().unwrap();
+        let sprite_pixel = self.pipeline.sprite_fifo.pop_front();
+
+        let color = if memory_bus.model.is_cgb() {
+            cgb_pixel_color(memory_bus, lcd_control, bg_pixel, sprite_pixel)
+        } else {
+            dmg_pixel_color(memory_bus, lcd_control, bg_pixel, sprite_pixel)
+        };
+
+        self.buffer[self.pipeline.x as usize + self.scanline as usize * 160] = color;
+        self.pipeline.x += 1;
+    }
+
+    /// OAM search: picks the first 10 sprites of OAM that overlap the current line.
+    ///
+    /// Sprites are then drawn by X, on DMG the lowest OAM index wins ties so the
+    /// sort must be stable. Sprites hidden off-screen still count towards the limit.
+    fn select_sprites(&mut self, memory_bus: &Memory) {
+        let lcd_control = LcdControl::from(memory_bus.read(LCD_CONTROL_ADDRESS));
+        let height = lcd_control.object_size.get_height();
+
+        self.pipeline.sprites.clear();
+
+        for i in 0..40 {
+            let address = OAM_ADDRESS + i * 4;
+            let sprite_data = [0, 1, 2, 3].map(|offset| memory_bus.ppu_read_oam(address + offset));
+            let sprite = Sprite::new_from_bytes(i as u8, sprite_data);
+
+            let row = self.scanline.wrapping_add(16).wrapping_sub(sprite.y);
+            if row < height {
+                self.pipeline.sprites.push(sprite);
+                if self.pipeline.sprites.len() == MAX_SPRITES_PER_LINE {
+                    break;
+                }
+            }
+        }
+
+        self.pipeline.sprites.sort_by_key(|sprite| sprite.x);
+    }
+}
+
+fn dmg_pixel_color(
+    memory_bus: &Memory,
+    lcd_control: &LcdControl,
+    bg_pixel: BgPixel,
+    sprite_pixel: Option<SpritePixel>,
+) -> Color {
+    let bg_color = if lcd_control.background_enable {
+        bg_pixel.color
+    } else {
+        0
+    };
+
+    match sprite_pixel {
+        Some(sprite_pixel)
+            if lcd_control.object_enable
+                && !sprite_pixel.is_transparent()
+                && !(sprite_pixel.bg_and_window_over && bg_color != 0) =>
+        {
+            Palette::sprite(memory_bus, sprite_pixel.palette).color(sprite_pixel.color)
+        }
+        _ if lcd_control.background_enable => Palette::background(memory_bus).color(bg_color),
+        _ => Color::White,
+    }
+}
+
+/// On CGB, LCDC bit 0 doesn't hide the background but gives sprites priority
+/// over it. Otherwise the background wins when either its attributes or the
+/// sprite ask for it, unless its color is 0.
+fn cgb_pixel_color(
+    memory_bus: &Memory,
+    lcd_control: &LcdControl,
+    bg_pixel: BgPixel,
+    sprite_pixel: Option<SpritePixel>,
+) -> Color {
+    let bg_wins = |sprite_pixel: &SpritePixel| {
+        lcd_control.background_enable
+            && bg_pixel.color != 0
+            && (bg_pixel.bg_over_sprites || sprite_pixel.bg_and_window_over)
+    };
+
+    match sprite_pixel {
+        Some(sprite_pixel)
+            if lcd_control.object_enable
+                && !sprite_pixel.is_transparent()
+                && !bg_wins(&sprite_pixel) =>
+        {
+            memory_bus
+                .obj_palettes
+                .color(sprite_pixel.cgb_palette, sprite_pixel.color)
+        }
+        _ => memory_bus
+            .bg_palettes
+            .color(bg_pixel.palette, bg_pixel.color),
+    }
+}
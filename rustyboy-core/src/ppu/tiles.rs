@@ -0,0 +1,137 @@
+use super::palette::SpritePalette;
+
+const TILE_DATA_BASE_ADDRESS: u16 = 0x8000;
+
+/// Decodes a row of a tile into the color index of each of its 8 pixels,
+/// from left to right.
+pub fn decode_tile_row(low: u8, high: u8) -> [u8; 8] {
+    let mut pixels = [0; 8];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let bit = 7 - i;
+        *pixel = (((high >> bit) & 0x01) << 1) | ((low >> bit) & 0x01);
+    }
+    pixels
+}
+
+const TILE_DATA_ROWS: usize = 0x1800 / 2;
+
+/// Tile data decoded to color indexes, kept in sync with VRAM on every write
+/// so the PPU never decodes a tile row while rendering.
+pub struct TileCache {
+    rows: Box<[[u8; 8]; TILE_DATA_ROWS]>,
+}
+
+impl TileCache {
+    pub fn new() -> TileCache {
+        TileCache {
+            rows: Box::new([[0; 8]; TILE_DATA_ROWS]),
+        }
+    }
+
+    /// Decodes again the row stored at `address` and `address + 1`.
+    pub fn update(&mut self, address: u16, low: u8, high: u8) {
+        self.rows[Self::row_index(address)] = decode_tile_row(low, high);
+    }
+
+    /// Color indexes of the row stored at `address` and `address + 1`.
+    pub fn row(&self, address: u16) -> [u8; 8] {
+        self.rows[Self::row_index(address)]
+    }
+
+    fn row_index(address: u16) -> usize {
+        (address - TILE_DATA_BASE_ADDRESS) as usize / 2
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub x: u8,
+    pub y: u8,
+    pub tile_index: u8,
+    pub sprite_flags: SpriteFlags,
+    pub oam_index: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteFlags {
+    pub bg_and_window_over: bool,
+    pub y_flip: bool,
+    pub x_flip: bool,
+    pub palette_number: SpritePalette,
+    /// CGB only
+    pub vram_bank: u8,
+    /// CGB only
+    pub cgb_palette: u8,
+}
+
+/// Attributes of a background or window tile, stored in VRAM bank 1 on CGB.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BgAttributes {
+    pub bg_over_sprites: bool,
+    pub y_flip: bool,
+    pub x_flip: bool,
+    pub vram_bank: u8,
+    pub palette: u8,
+}
+
+impl Sprite {
+    pub fn new_from_bytes(oam_index: u8, oam_data: [u8; 4]) -> Self {
+        Sprite {
+            y: oam_data[0],
+            x: oam_data[1],
+            tile_index: oam_data[2],
+            sprite_flags: SpriteFlags::from(oam_data[3]),
+            oam_index,
+        }
+    }
+
+    /// Address of the row of the sprite tile drawn on `scanline`.
+    ///
+    /// In 8x16 mode the top tile index is the one in OAM with bit 0 cleared,
+    /// the bottom one directly follows it in VRAM.
+    pub fn tile_row_address(&self, scanline: u8, height: u8) -> u16 {
+        let row = scanline.wrapping_add(16).wrapping_sub(self.y) & (height - 1);
+        let row = if self.sprite_flags.y_flip {
+            height - 1 - row
+        } else {
+            row
+        };
+
+        let tile_index = if height == 16 {
+            self.tile_index & 0xfe
+        } else {
+            self.tile_index
+        };
+
+        TILE_DATA_BASE_ADDRESS + tile_index as u16 * 16 + row as u16 * 2
+    }
+}
+
+impl std::convert::From<u8> for SpriteFlags {
+    fn from(value: u8) -> Self {
+        SpriteFlags {
+            bg_and_window_over: (value & 0x80) != 0,
+            y_flip: (value & 0x40) != 0,
+            x_flip: (value & 0x20) != 0,
+            palette_number: match (value & 0x10) >> 4 {
+                0 => SpritePalette::OBP0,
+                1 => SpritePalette::OBP1,
+                _ => panic!("Invalid sprite palette"),
+            },
+            vram_bank: (value & 0x08) >> 3,
+            cgb_palette: value & 0x07,
+        }
+    }
+}
+
+impl std::convert::From<u8> for BgAttributes {
+    fn from(value: u8) -> Self {
+        BgAttributes {
+            bg_over_sprites: (value & 0x80) != 0,
+            y_flip: (value & 0x40) != 0,
+            x_flip: (value & 0x20) != 0,
+            vram_bank: (value & 0x08) >> 3,
+            palette: value & 0x07,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use std::{fs::File, io::BufWriter, io::Write, path::Path};
+
+use crate::ppu::palette::{Color, DisplayPalette};
+use crate::sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
+
+const SCREEN_WIDTH: u32 = 160;
+const SCREEN_HEIGHT: u32 = 144;
+
+/// Encodes a frame as a PNG image, mapping the shades through `palette`.
+pub fn write_png<W: Write>(
+    writer: W,
+    buffer: &[Color; 160 * 144],
+    palette: &DisplayPalette,
+) -> Result<()> {
+    encode_png(writer, buffer, SCREEN_WIDTH, SCREEN_HEIGHT, palette)
+}
+
+/// Saves a frame to a PNG file, mapping the shades through `palette`.
+pub fn save_png<P: AsRef<Path>>(
+    path: P,
+    buffer: &[Color; 160 * 144],
+    palette: &DisplayPalette,
+) -> Result<()> {
+    let file = File::create(path)?;
+    write_png(BufWriter::new(file), buffer, palette)
+}
+
+/// Encodes a SGB screen, with its border, as a PNG image.
+pub fn write_sgb_png<W: Write>(
+    writer: W,
+    buffer: &[Color; SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT],
+    palette: &DisplayPalette,
+) -> Result<()> {
+    encode_png(
+        writer,
+        buffer,
+        SGB_SCREEN_WIDTH as u32,
+        SGB_SCREEN_HEIGHT as u32,
+        palette,
+    )
+}
+
+/// Saves a SGB screen, with its border, to a PNG file.
+pub fn save_sgb_png<P: AsRef<Path>>(
+    path: P,
+    buffer: &[Color; SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT],
+    palette: &DisplayPalette,
+) -> Result<()> {
+    let file = File::create(path)?;
+    write_sgb_png(BufWriter::new(file), buffer, palette)
+}
+
+fn encode_png<W: Write>(
+    writer: W,
+    buffer: &[Color],
+    width: u32,
+    height: u32,
+    palette: &DisplayPalette,
+) -> Result<()> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&palette.frame_to_rgb(buffer))?;
+
+    Ok(())
+}
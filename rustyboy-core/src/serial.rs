@@ -0,0 +1,148 @@
+pub mod cable;
+pub mod link;
+
+use std::io::Write;
+
+/// M-cycles taken to shift one bit with the internal clock, at 8192 Hz.
+const BIT_CYCLES: u16 = 128;
+
+/// A peripheral plugged into the serial port.
+///
+/// Bytes are exchanged whole: the Gameboy still takes 8 bit periods to shift
+/// them in and out when it drives the clock. Devices are `Send` so that
+/// [`Hardware`](crate::Hardware) can move to another thread.
+pub trait SerialDevice: Send {
+    /// The Gameboy starts a transfer with its internal clock and sends `byte`.
+    ///
+    /// Returns the byte shifted in from the device.
+    fn transfer(&mut self, byte: u8) -> u8;
+
+    /// Called on every bit period while the Gameboy doesn't drive a transfer.
+    /// `byte` is the content of SB when a transfer is waiting for an external
+    /// clock, `None` otherwise.
+    ///
+    /// Returns the byte shifted in when the device clocked a transfer, only
+    /// devices providing a clock need to implement it.
+    fn poll(&mut self, _byte: Option<u8>) -> Option<u8> {
+        None
+    }
+}
+
+/// Nothing plugged in, the input line stays high so 0xff is shifted in.
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn transfer(&mut self, _byte: u8) -> u8 {
+        0xff
+    }
+}
+
+/// Serial port: SB and SC, shifting bits at 8192 Hz with the internal clock.
+///
+/// The bytes sent are recorded whatever device is plugged in, test ROMs print
+/// their results this way.
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+    /// Byte being shifted in, MSB first
+    incoming: u8,
+    bits_left: u8,
+    cycles: u16,
+    device: Box<dyn SerialDevice>,
+    output: Vec<u8>,
+    /// Also prints every byte to stdout as it is sent
+    pub echo: bool,
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            sb: 0,
+            sc: 0,
+            incoming: 0xff,
+            bits_left: 0,
+            cycles: 0,
+            device: Box::new(Disconnected),
+            output: vec![],
+            echo: false,
+        }
+    }
+
+    pub fn connect(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = device;
+    }
+
+    /// Bytes sent so far.
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn read_sb(&self) -> u8 {
+        self.sb
+    }
+
+    pub fn write_sb(&mut self, value: u8) {
+        self.sb = value;
+    }
+
+    pub fn read_sc(&self) -> u8 {
+        0x7e | self.sc
+    }
+
+    pub fn write_sc(&mut self, value: u8) {
+        self.sc = value & 0x81;
+        self.cycles = 0;
+
+        if self.is_internal_transfer() {
+            self.record(self.sb);
+            self.incoming = self.device.transfer(self.sb);
+            self.bits_left = 8;
+        }
+    }
+
+    /// Advances the serial port by one M-cycle.
+    ///
+    /// Returns `true` when a transfer completes, requesting the serial interrupt.
+    pub fn step(&mut self) -> bool {
+        self.cycles += 1;
+        if self.cycles < BIT_CYCLES {
+            return false;
+        }
+        self.cycles = 0;
+
+        if self.is_internal_transfer() {
+            self.sb = (self.sb << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+            if self.bits_left == 0 {
+                self.sc &= 0x7f;
+                return true;
+            }
+        } else {
+            // Only a transfer waiting for the external clock takes the byte
+            let waiting = (self.sc == 0x80).then_some(self.sb);
+            if let (Some(sent), Some(byte)) = (waiting, self.device.poll(waiting)) {
+                self.record(sent);
+                self.sb = byte;
+                self.sc &= 0x7f;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn record(&mut self, byte: u8) {
+        self.output.push(byte);
+
+        if self.echo {
+            let mut stdout = std::io::stdout();
+            // Echo is best effort, a closed stdout must not stop the emulation
+            let _ = stdout.write_all(&[byte]).and_then(|_| stdout.flush());
+        }
+    }
+
+    fn is_internal_transfer(&self) -> bool {
+        self.sc == 0x81
+    }
+}
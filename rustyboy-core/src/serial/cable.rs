@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex};
+
+use super::SerialDevice;
+
+/// Serial port state seen through the cable.
+#[derive(Default)]
+struct Port {
+    /// SB of a transfer waiting for the external clock
+    waiting: Option<u8>,
+    /// Byte shifted in by the other Gameboy, not yet picked up
+    received: Option<u8>,
+}
+
+/// One end of an in-memory link cable, see [`cable`].
+pub struct CableEnd {
+    ports: Arc<Mutex<[Port; 2]>>,
+    side: usize,
+}
+
+/// Link cable between two Gameboys emulated in the same process.
+///
+/// The Gameboy driving the clock exchanges bytes with the other one if it
+/// waits for a transfer, otherwise it receives 0xff.
+pub fn cable() -> (CableEnd, CableEnd) {
+    let ports = Arc::new(Mutex::new([Port::default(), Port::default()]));
+    let first = CableEnd {
+        ports: ports.clone(),
+        side: 0,
+    };
+    let second = CableEnd { ports, side: 1 };
+
+    (first, second)
+}
+
+impl SerialDevice for CableEnd {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let mut ports = self.ports.lock().unwrap();
+        let peer = &mut ports[1 - self.side];
+
+        match peer.waiting.take() {
+            Some(received) => {
+                peer.received = Some(byte);
+                received
+            }
+            None => 0xff,
+        }
+    }
+
+    fn poll(&mut self, byte: Option<u8>) -> Option<u8> {
+        let mut ports = self.ports.lock().unwrap();
+        let port = &mut ports[self.side];
+
+        let received = port.received.take();
+        port.waiting = if received.is_some() { None } else { byte };
+        byte.and(received)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::{Serial, BIT_CYCLES};
+
+    fn linked_serials() -> (Serial, Serial) {
+        let (first, second) = cable();
+        let mut master = Serial::new();
+        let mut slave = Serial::new();
+        master.connect(Box::new(first));
+        slave.connect(Box::new(second));
+
+        (master, slave)
+    }
+
+    /// Steps both ports for `cycles` M-cycles, returns whether their transfer completed.
+    fn step_both(master: &mut Serial, slave: &mut Serial, cycles: u16) -> (bool, bool) {
+        let mut completed = (false, false);
+        for _ in 0..cycles {
+            completed.0 |= master.step();
+            completed.1 |= slave.step();
+        }
+        completed
+    }
+
+    #[test]
+    fn transfer_exchanges_bytes_with_a_waiting_gameboy() {
+        let (mut master, mut slave) = linked_serials();
+        slave.write_sb(0x99);
+        slave.write_sc(0x80);
+        step_both(&mut master, &mut slave, BIT_CYCLES);
+
+        master.write_sb(0x42);
+        master.write_sc(0x81);
+        let completed = step_both(&mut master, &mut slave, 8 * BIT_CYCLES);
+
+        assert_eq!(completed, (true, true));
+        assert_eq!(master.read_sb(), 0x99);
+        assert_eq!(slave.read_sb(), 0x42);
+        assert_eq!(master.read_sc() & 0x80, 0);
+        assert_eq!(slave.read_sc() & 0x80, 0);
+    }
+
+    #[test]
+    fn transfer_without_a_waiting_gameboy_receives_0xff() {
+        let (mut master, mut slave) = linked_serials();
+        slave.write_sb(0x99);
+
+        master.write_sb(0x42);
+        master.write_sc(0x81);
+        let completed = step_both(&mut master, &mut slave, 8 * BIT_CYCLES);
+
+        assert_eq!(completed, (true, false));
+        assert_eq!(master.read_sb(), 0xff);
+        assert_eq!(slave.read_sb(), 0x99);
+    }
+}
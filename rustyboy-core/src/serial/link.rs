@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use super::SerialDevice;
+
+// Every message is 2 bytes: its kind then the byte transferred
+const TRANSFER: u8 = 0x01;
+const REPLY: u8 = 0x02;
+
+/// How long the Gameboy driving the clock waits for the other one to reply.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Link cable to another emulator over TCP.
+///
+/// The Gameboy driving the clock sends its byte and waits for the other one
+/// to answer with the content of its SB, so both emulators are in lockstep
+/// around each transfer. An emulator that isn't waiting for a transfer, or
+/// that drives the clock too, answers 0xff.
+pub struct TcpLink {
+    stream: Option<TcpStream>,
+    buffer: Vec<u8>,
+    /// Reads on the stream wait for data, until the reply timeout
+    blocking: bool,
+}
+
+impl TcpLink {
+    /// Waits for the other emulator to connect on `address`.
+    pub fn listen(address: &str) -> Result<TcpLink> {
+        let listener =
+            TcpListener::bind(address).with_context(|| format!("Cannot listen on {}", address))?;
+        let (stream, _) = listener.accept()?;
+        TcpLink::new(stream)
+    }
+
+    /// Connects to the other emulator, listening on `address`.
+    pub fn connect(address: &str) -> Result<TcpLink> {
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("Cannot connect to {}", address))?;
+        TcpLink::new(stream)
+    }
+
+    fn new(stream: TcpStream) -> Result<TcpLink> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+        Ok(TcpLink {
+            stream: Some(stream),
+            buffer: vec![],
+            blocking: false,
+        })
+    }
+
+    fn send(&mut self, kind: u8, byte: u8) {
+        let result = match &mut self.stream {
+            Some(stream) => stream.write_all(&[kind, byte]),
+            None => return,
+        };
+        if result.is_err() {
+            self.disconnect();
+        }
+    }
+
+    /// Next message from the other emulator, waiting for it up to the reply
+    /// timeout when `blocking`.
+    fn receive(&mut self, blocking: bool) -> Option<(u8, u8)> {
+        while self.buffer.len() < 2 {
+            let stream = self.stream.as_mut()?;
+            let mut data = [0; 2];
+
+            if blocking != self.blocking {
+                if stream.set_nonblocking(!blocking).is_err() {
+                    self.disconnect();
+                    return None;
+                }
+                self.blocking = blocking;
+            }
+            match stream.read(&mut data) {
+                Ok(0) => {
+                    self.disconnect();
+                    return None;
+                }
+                Ok(length) => self.buffer.extend_from_slice(&data[..length]),
+                // Timed out reads are reported as either kind depending on the platform
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    return None
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.disconnect();
+                    return None;
+                }
+            }
+        }
+
+        let message = (self.buffer[0], self.buffer[1]);
+        self.buffer.drain(..2);
+        Some(message)
+    }
+
+    fn disconnect(&mut self) {
+        if self.stream.take().is_some() {
+            eprintln!("Link cable disconnected");
+        }
+    }
+}
+
+impl SerialDevice for TcpLink {
+    /// Without a reply in time, 0xff is shifted in as if nothing was plugged in.
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.send(TRANSFER, byte);
+
+        while let Some((kind, received)) = self.receive(true) {
+            match kind {
+                REPLY => return received,
+                // Both sides drive the clock, neither gets a byte
+                _ => self.send(REPLY, 0xff),
+            }
+        }
+
+        0xff
+    }
+
+    fn poll(&mut self, byte: Option<u8>) -> Option<u8> {
+        while let Some((kind, received)) = self.receive(false) {
+            if kind == TRANSFER {
+                self.send(REPLY, byte.unwrap_or(0xff));
+                return byte.map(|_| received);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+
+    /// Two links connected to each other over the loopback interface.
+    fn linked_pair() -> (TcpLink, TcpLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let connecting = thread::spawn(move || TcpStream::connect(address).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+
+        let first = TcpLink::new(stream).unwrap();
+        let second = TcpLink::new(connecting.join().unwrap()).unwrap();
+        (first, second)
+    }
+
+    /// Polls with `byte` waiting for a transfer until the other side clocks it.
+    fn wait_for_transfer(link: &mut TcpLink, byte: u8) -> u8 {
+        loop {
+            if let Some(received) = link.poll(Some(byte)) {
+                return received;
+            }
+        }
+    }
+
+    #[test]
+    fn links_exchange_a_byte_each_way() {
+        let (mut master, mut slave) = linked_pair();
+        let slave = thread::spawn(move || wait_for_transfer(&mut slave, 0x99));
+
+        assert_eq!(master.transfer(0x42), 0x99);
+        assert_eq!(slave.join().unwrap(), 0x42);
+    }
+
+    #[test]
+    fn transfer_to_a_dropped_peer_receives_0xff() {
+        let (mut master, slave) = linked_pair();
+        drop(slave);
+
+        assert_eq!(master.transfer(0x42), 0xff);
+        assert_eq!(master.transfer(0x42), 0xff);
+    }
+
+    #[test]
+    fn transfer_without_a_reply_times_out() {
+        let (mut master, _slave) = linked_pair();
+
+        let start = Instant::now();
+        assert_eq!(master.transfer(0x42), 0xff);
+        assert!(start.elapsed() < 2 * REPLY_TIMEOUT);
+    }
+}
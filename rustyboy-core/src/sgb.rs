@@ -0,0 +1,518 @@
+pub mod packets;
+
+use std::cmp::Ordering;
+
+use crate::ppu::palette::Color;
+
+pub const SGB_SCREEN_WIDTH: usize = 256;
+pub const SGB_SCREEN_HEIGHT: usize = 224;
+
+// Position of the Gameboy screen inside the border
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+const ATTRIBUTE_COLUMNS: usize = 20;
+const ATTRIBUTE_ROWS: usize = 18;
+const BORDER_COLUMNS: usize = 32;
+const BORDER_ROWS: usize = 28;
+const BORDER_TILE_SIZE: usize = 32;
+const TRANSFER_SIZE: usize = 0x1000;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0a;
+const PAL_TRN: u8 = 0x0b;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const MASK_EN: u8 = 0x17;
+
+/// Palette used until the game sets its own.
+const DEFAULT_PALETTE: [u16; 4] = [0x67bf, 0x265b, 0x10b5, 0x2866];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mask {
+    Cancel,
+    Freeze,
+    Black,
+    Color0,
+}
+
+/// Data copied from VRAM on the next frame, by drawing it on screen.
+#[derive(Debug, Clone, Copy)]
+enum Transfer {
+    /// Border tiles, starting from the given tile
+    BorderTiles(usize),
+    /// Border map and palettes
+    BorderMap,
+    SystemPalettes,
+}
+
+/// Super Game Boy: colors the Gameboy screen with 4 palettes, picked for
+/// each 8x8 area, and frames it with a border.
+pub struct Sgb {
+    palettes: [[u16; 4]; 4],
+    attributes: [u8; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+    mask: Mask,
+    transfer: Option<Transfer>,
+    system_palettes: Box<[[u16; 4]; 512]>,
+    border_tiles: Box<[u8; 256 * BORDER_TILE_SIZE]>,
+    border_map: Box<[u16; BORDER_COLUMNS * BORDER_ROWS]>,
+    border_palettes: [[u16; 16]; 4],
+    frame: [Color; 160 * 144],
+    /// Last frame inside its border
+    pub screen: Box<[Color; SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT]>,
+}
+
+impl Sgb {
+    pub fn new() -> Sgb {
+        Sgb {
+            palettes: [DEFAULT_PALETTE; 4],
+            attributes: [0; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+            mask: Mask::Cancel,
+            transfer: None,
+            system_palettes: Box::new([[0; 4]; 512]),
+            border_tiles: Box::new([0; 256 * BORDER_TILE_SIZE]),
+            border_map: Box::new([0; BORDER_COLUMNS * BORDER_ROWS]),
+            border_palettes: [[0; 16]; 4],
+            frame: [Color::Rgb(DEFAULT_PALETTE[0]); 160 * 144],
+            screen: Box::new(
+                [Color::Rgb(DEFAULT_PALETTE[0]); SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT],
+            ),
+        }
+    }
+
+    /// Executes a command received through JOYP, unsupported ones are ignored.
+    pub fn execute(&mut self, command: &[u8]) {
+        match command[0] >> 3 {
+            PAL01 => self.set_palettes(0, 1, command),
+            PAL23 => self.set_palettes(2, 3, command),
+            PAL03 => self.set_palettes(0, 3, command),
+            PAL12 => self.set_palettes(1, 2, command),
+            ATTR_BLK => self.attribute_blocks(command),
+            ATTR_LIN => self.attribute_lines(command),
+            ATTR_DIV => self.attribute_division(command),
+            ATTR_CHR => self.attribute_characters(command),
+            PAL_SET => self.set_system_palettes(command),
+            PAL_TRN => self.transfer = Some(Transfer::SystemPalettes),
+            CHR_TRN => {
+                let first_tile = if command[1] & 0x01 == 0 { 0x00 } else { 0x80 };
+                self.transfer = Some(Transfer::BorderTiles(first_tile));
+            }
+            PCT_TRN => self.transfer = Some(Transfer::BorderMap),
+            MASK_EN => {
+                self.mask = match command[1] & 0x03 {
+                    0x00 => Mask::Cancel,
+                    0x01 => Mask::Freeze,
+                    0x02 => Mask::Black,
+                    _ => Mask::Color0,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Colors a frame produced by the PPU in place and updates the screen with it.
+    ///
+    /// A pending transfer reads its data from the frame first, as long as the
+    /// LCD `rendered` it: blank frames while the LCD is off don't count.
+    pub fn colorize(&mut self, buffer: &mut [Color; 160 * 144], rendered: bool) {
+        if rendered {
+            if let Some(transfer) = self.transfer.take() {
+                self.receive_transfer(transfer, &transfer_data(buffer));
+            }
+        }
+
+        match self.mask {
+            Mask::Cancel => {
+                for (i, (pixel, color)) in self.frame.iter_mut().zip(buffer.iter()).enumerate() {
+                    let (x, y) = (i % 160, i / 160);
+                    let palette = self.attributes[(y / 8) * ATTRIBUTE_COLUMNS + x / 8] as usize;
+                    *pixel = match shade(*color) {
+                        // Color 0 is shared by all palettes
+                        0 => Color::Rgb(self.palettes[0][0]),
+                        shade => Color::Rgb(self.palettes[palette][shade as usize]),
+                    };
+                }
+            }
+            Mask::Freeze => {}
+            Mask::Black => self.frame = [Color::Rgb(0); 160 * 144],
+            Mask::Color0 => self.frame = [Color::Rgb(self.palettes[0][0]); 160 * 144],
+        }
+
+        self.draw_screen();
+        buffer.copy_from_slice(&self.frame);
+    }
+
+    fn draw_screen(&mut self) {
+        let backdrop = Color::Rgb(self.palettes[0][0]);
+
+        for y in 0..SGB_SCREEN_HEIGHT {
+            for x in 0..SGB_SCREEN_WIDTH {
+                let pixel = match self.border_pixel(x, y) {
+                    Some(color) => Color::Rgb(color),
+                    None if (SCREEN_X..SCREEN_X + 160).contains(&x)
+                        && (SCREEN_Y..SCREEN_Y + 144).contains(&y) =>
+                    {
+                        self.frame[(y - SCREEN_Y) * 160 + x - SCREEN_X]
+                    }
+                    None => backdrop,
+                };
+                self.screen[y * SGB_SCREEN_WIDTH + x] = pixel;
+            }
+        }
+    }
+
+    /// Border color at a screen position, `None` where it is transparent.
+    fn border_pixel(&self, x: usize, y: usize) -> Option<u16> {
+        let entry = self.border_map[(y / 8) * BORDER_COLUMNS + x / 8];
+        let tile = (entry & 0xff) as usize;
+        let palette = (entry >> 10) as usize & 0x03;
+
+        let column = if entry & 0x4000 != 0 {
+            x % 8
+        } else {
+            7 - x % 8
+        };
+        let row = if entry & 0x8000 != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+
+        // 4 bits per pixel, bit planes 0 and 1 come first then 2 and 3
+        let data = &self.border_tiles[tile * BORDER_TILE_SIZE..];
+        let index = [
+            data[row * 2],
+            data[row * 2 + 1],
+            data[16 + row * 2],
+            data[17 + row * 2],
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |index, (plane, byte)| {
+            index | ((byte >> column) & 0x01) << plane
+        });
+
+        if index == 0 {
+            None
+        } else {
+            Some(self.border_palettes[palette][index as usize])
+        }
+    }
+
+    fn receive_transfer(&mut self, transfer: Transfer, data: &[u8]) {
+        match transfer {
+            Transfer::BorderTiles(first_tile) => {
+                let start = first_tile * BORDER_TILE_SIZE;
+                self.border_tiles[start..start + TRANSFER_SIZE].copy_from_slice(data);
+            }
+            Transfer::BorderMap => {
+                for (entry, bytes) in self.border_map.iter_mut().zip(data.chunks_exact(2)) {
+                    *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+                let palettes = data[0x800..0x880].chunks_exact(2).map(read_color);
+                for (i, color) in palettes.enumerate() {
+                    self.border_palettes[i / 16][i % 16] = color;
+                }
+            }
+            Transfer::SystemPalettes => {
+                let colors = data.chunks_exact(2).map(read_color);
+                for (i, color) in colors.enumerate() {
+                    self.system_palettes[i / 4][i % 4] = color;
+                }
+            }
+        }
+    }
+
+    /// PAL01, PAL23, PAL03 and PAL12: color 0 then 3 colors for each palette.
+    fn set_palettes(&mut self, first: usize, second: usize, command: &[u8]) {
+        let colors: Vec<u16> = command[1..15].chunks_exact(2).map(read_color).collect();
+
+        for palette in &mut self.palettes {
+            palette[0] = colors[0];
+        }
+        self.palettes[first][1..].copy_from_slice(&colors[1..4]);
+        self.palettes[second][1..].copy_from_slice(&colors[4..7]);
+    }
+
+    /// PAL_SET: copies 4 of the palettes received with PAL_TRN.
+    fn set_system_palettes(&mut self, command: &[u8]) {
+        for (palette, bytes) in self.palettes.iter_mut().zip(command[1..9].chunks_exact(2)) {
+            let index = u16::from_le_bytes([bytes[0], bytes[1]]) as usize & 0x1ff;
+            *palette = self.system_palettes[index];
+        }
+
+        if command[9] & 0x40 != 0 {
+            self.mask = Mask::Cancel;
+        }
+    }
+
+    /// ATTR_BLK: sets the palette inside, on the border and outside of rectangles.
+    fn attribute_blocks(&mut self, command: &[u8]) {
+        let count = command[1] as usize;
+
+        for block in command[2..].chunks_exact(6).take(count) {
+            let palettes = block[1];
+            let (inside, mut border, outside) = (
+                palettes & 0x03,
+                (palettes >> 2) & 0x03,
+                (palettes >> 4) & 0x03,
+            );
+            // With only the inside or the outside changed, the border follows it
+            let control = match block[0] & 0x07 {
+                0x01 => {
+                    border = inside;
+                    0x03
+                }
+                0x04 => {
+                    border = outside;
+                    0x06
+                }
+                control => control,
+            };
+
+            let (x1, y1, x2, y2) = (
+                block[2] as usize,
+                block[3] as usize,
+                block[4] as usize,
+                block[5] as usize,
+            );
+            self.update_attributes(|x, y| {
+                if x > x1 && x < x2 && y > y1 && y < y2 {
+                    (control & 0x01 != 0).then_some(inside)
+                } else if x < x1 || x > x2 || y < y1 || y > y2 {
+                    (control & 0x04 != 0).then_some(outside)
+                } else {
+                    (control & 0x02 != 0).then_some(border)
+                }
+            });
+        }
+    }
+
+    /// ATTR_LIN: sets the palette of whole rows or columns.
+    fn attribute_lines(&mut self, command: &[u8]) {
+        let count = command[1] as usize;
+
+        for &line in command[2..].iter().take(count) {
+            let index = (line & 0x1f) as usize;
+            let palette = (line >> 5) & 0x03;
+            let horizontal = line & 0x80 != 0;
+
+            self.update_attributes(|x, y| {
+                let position = if horizontal { y } else { x };
+                (position == index).then_some(palette)
+            });
+        }
+    }
+
+    /// ATTR_DIV: splits the screen in two along a row or a column.
+    fn attribute_division(&mut self, command: &[u8]) {
+        let palettes = command[1];
+        let (after, before, on_line) = (
+            palettes & 0x03,
+            (palettes >> 2) & 0x03,
+            (palettes >> 4) & 0x03,
+        );
+        let horizontal = palettes & 0x40 != 0;
+        let line = command[2] as usize;
+
+        self.update_attributes(|x, y| {
+            let position = if horizontal { y } else { x };
+            Some(match position.cmp(&line) {
+                Ordering::Less => before,
+                Ordering::Equal => on_line,
+                Ordering::Greater => after,
+            })
+        });
+    }
+
+    /// ATTR_CHR: sets the palette of areas one by one, 4 per byte.
+    fn attribute_characters(&mut self, command: &[u8]) {
+        let (mut x, mut y) = (command[1] as usize, command[2] as usize);
+        let count = u16::from_le_bytes([command[3], command[4]]) as usize;
+        let vertical = command[5] & 0x01 != 0;
+
+        for i in 0..count.min(ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS) {
+            let Some(byte) = command.get(6 + i / 4) else {
+                break;
+            };
+            if x < ATTRIBUTE_COLUMNS && y < ATTRIBUTE_ROWS {
+                self.attributes[y * ATTRIBUTE_COLUMNS + x] = (byte >> (6 - (i % 4) * 2)) & 0x03;
+            }
+
+            if vertical {
+                y += 1;
+                if y == ATTRIBUTE_ROWS {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == ATTRIBUTE_COLUMNS {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// Sets the palette of every area for which `palette` returns one.
+    fn update_attributes(&mut self, palette: impl Fn(usize, usize) -> Option<u8>) {
+        for y in 0..ATTRIBUTE_ROWS {
+            for x in 0..ATTRIBUTE_COLUMNS {
+                if let Some(palette) = palette(x, y) {
+                    self.attributes[y * ATTRIBUTE_COLUMNS + x] = palette;
+                }
+            }
+        }
+    }
+}
+
+fn read_color(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]]) & 0x7fff
+}
+
+/// Shade of a DMG pixel, as sent by the Gameboy to the SGB.
+fn shade(color: Color) -> u8 {
+    match color {
+        Color::Transparent | Color::White | Color::Rgb(_) => 0,
+        Color::LightGray => 1,
+        Color::DarkGray => 2,
+        Color::Black => 3,
+        Color::Sprite(_, shade) => shade,
+    }
+}
+
+/// Reads back the 4KB of VRAM shown on screen during a transfer: the first
+/// 256 tiles of the screen, row by row, encoded as 2 bits per pixel.
+fn transfer_data(buffer: &[Color; 160 * 144]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(TRANSFER_SIZE);
+
+    for tile in 0..256 {
+        let (tile_x, tile_y) = (tile % ATTRIBUTE_COLUMNS * 8, tile / ATTRIBUTE_COLUMNS * 8);
+        for row in 0..8 {
+            let (mut low, mut high) = (0, 0);
+            for column in 0..8 {
+                let shade = shade(buffer[(tile_y + row) * 160 + tile_x + column]);
+                low |= (shade & 0x01) << (7 - column);
+                high |= (shade >> 1) << (7 - column);
+            }
+            data.push(low);
+            data.push(high);
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Command packet starting with `bytes`, padded with zeros.
+    fn packet(bytes: &[u8]) -> Vec<u8> {
+        let mut packet = bytes.to_vec();
+        packet.resize(16, 0);
+        packet
+    }
+
+    /// PAL01 giving palette 0 the colors 0x11-0x13 and palette 1 0x21-0x23,
+    /// with 0x10 shared as color 0.
+    fn pal01() -> Vec<u8> {
+        packet(&[
+            PAL01 << 3 | 1,
+            0x10,
+            0,
+            0x11,
+            0,
+            0x12,
+            0,
+            0x13,
+            0,
+            0x21,
+            0,
+            0x22,
+            0,
+            0x23,
+            0,
+        ])
+    }
+
+    /// Color of a pixel of a colorized frame.
+    fn pixel(buffer: &[Color; 160 * 144], x: usize, y: usize) -> u16 {
+        match buffer[y * 160 + x] {
+            Color::Rgb(color) => color,
+            color => panic!("{:?} is not colorized", color),
+        }
+    }
+
+    #[test]
+    fn pal01_colors_the_frame() {
+        let mut sgb = Sgb::new();
+        sgb.execute(&pal01());
+
+        let mut buffer = [Color::LightGray; 160 * 144];
+        buffer[0] = Color::White;
+        sgb.colorize(&mut buffer, true);
+
+        assert_eq!(pixel(&buffer, 0, 0), 0x10);
+        assert_eq!(pixel(&buffer, 1, 0), 0x11);
+    }
+
+    #[test]
+    fn attr_blk_picks_the_palette_of_each_area() {
+        let mut sgb = Sgb::new();
+        sgb.execute(&pal01());
+        // One block from (0,0) to (2,2), palette 1 inside and on its border
+        sgb.execute(&packet(&[ATTR_BLK << 3 | 1, 1, 0x01, 0x01, 0, 0, 2, 2]));
+
+        let mut buffer = [Color::Black; 160 * 144];
+        sgb.colorize(&mut buffer, true);
+
+        assert_eq!(pixel(&buffer, 8, 8), 0x23);
+        assert_eq!(pixel(&buffer, 16, 16), 0x23);
+        assert_eq!(pixel(&buffer, 24, 24), 0x13);
+    }
+
+    #[test]
+    fn mask_en_freezes_or_blanks_the_screen() {
+        let mut sgb = Sgb::new();
+        sgb.execute(&pal01());
+        let mut buffer = [Color::LightGray; 160 * 144];
+        sgb.colorize(&mut buffer, true);
+
+        sgb.execute(&packet(&[MASK_EN << 3 | 1, 0x01]));
+        let mut buffer = [Color::Black; 160 * 144];
+        sgb.colorize(&mut buffer, true);
+        assert_eq!(pixel(&buffer, 0, 0), 0x11);
+
+        sgb.execute(&packet(&[MASK_EN << 3 | 1, 0x02]));
+        sgb.colorize(&mut buffer, true);
+        assert_eq!(pixel(&buffer, 0, 0), 0);
+
+        sgb.execute(&packet(&[MASK_EN << 3 | 1, 0x00]));
+        let mut buffer = [Color::Black; 160 * 144];
+        sgb.colorize(&mut buffer, true);
+        assert_eq!(pixel(&buffer, 0, 0), 0x13);
+    }
+
+    #[test]
+    fn transfers_wait_for_a_frame_rendered_by_the_lcd() {
+        let mut sgb = Sgb::new();
+        sgb.execute(&packet(&[PAL_TRN << 3 | 1]));
+
+        let mut buffer = [Color::White; 160 * 144];
+        sgb.colorize(&mut buffer, false);
+        assert!(sgb.transfer.is_some());
+
+        let mut buffer = [Color::White; 160 * 144];
+        sgb.colorize(&mut buffer, true);
+        assert!(sgb.transfer.is_none());
+    }
+}
@@ -0,0 +1,166 @@
+const PACKET_SIZE: usize = 16;
+const MLT_REQ: u8 = 0x11;
+
+/// Receives SGB command packets, sent bit by bit through the P14 and P15
+/// lines of JOYP.
+///
+/// A packet starts with both lines low, then every bit is a pulse of P14 (0)
+/// or P15 (1) followed by both lines high. Bytes are sent LSB first. The first
+/// byte of a command holds its code and how many packets it spans.
+///
+/// MLT_REQ is handled here as it changes what JOYP reads: with several
+/// players, reading JOYP with both lines high returns the current player.
+pub struct PacketReceiver {
+    lines: u8,
+    receiving: bool,
+    bit_index: usize,
+    packet: [u8; PACKET_SIZE],
+    command: Vec<u8>,
+    /// Commands fully received, waiting to be executed
+    pub commands: Vec<Vec<u8>>,
+    players: u8,
+    player: u8,
+}
+
+impl PacketReceiver {
+    pub fn new() -> PacketReceiver {
+        PacketReceiver {
+            lines: 0x30,
+            receiving: false,
+            bit_index: 0,
+            packet: [0; PACKET_SIZE],
+            command: vec![],
+            commands: vec![],
+            players: 1,
+            player: 0,
+        }
+    }
+
+    /// Handles a JOYP write, `value` holds P14 in bit 4 and P15 in bit 5.
+    pub fn write(&mut self, value: u8) {
+        let lines = value & 0x30;
+        let previous = std::mem::replace(&mut self.lines, lines);
+        if lines == previous {
+            return;
+        }
+
+        match lines {
+            0x00 => {
+                self.receiving = true;
+                self.bit_index = 0;
+                self.packet = [0; PACKET_SIZE];
+            }
+            0x10 | 0x20 if previous == 0x30 && self.receiving => self.receive_bit(lines == 0x10),
+            0x30 if previous == 0x10 && self.players > 1 => {
+                self.player = (self.player + 1) % self.players;
+            }
+            _ => {}
+        }
+    }
+
+    /// Lower nibble of JOYP when both lines are high, `None` with a single player.
+    pub fn joypad_id(&self) -> Option<u8> {
+        if self.players > 1 && self.lines == 0x30 {
+            Some(0x0f - self.player)
+        } else {
+            None
+        }
+    }
+
+    fn receive_bit(&mut self, bit: bool) {
+        self.packet[self.bit_index / 8] |= (bit as u8) << (self.bit_index % 8);
+        self.bit_index += 1;
+
+        if self.bit_index == PACKET_SIZE * 8 {
+            // The stop bit that follows is ignored
+            self.receiving = false;
+            self.receive_packet();
+        }
+    }
+
+    fn receive_packet(&mut self) {
+        self.command.extend_from_slice(&self.packet);
+
+        let length = (self.command[0] & 0x07).max(1) as usize;
+        if self.command.len() < length * PACKET_SIZE {
+            return;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        if command[0] >> 3 == MLT_REQ {
+            self.players = match command[1] & 0x03 {
+                0x01 => 2,
+                0x03 => 4,
+                _ => 1,
+            };
+            self.player = 0;
+        }
+        self.commands.push(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends a packet through JOYP: a reset pulse, the bits LSB first then a
+    /// stop bit.
+    fn send_packet(receiver: &mut PacketReceiver, packet: &[u8; PACKET_SIZE]) {
+        receiver.write(0x00);
+        receiver.write(0x30);
+        for byte in packet {
+            for bit in 0..8 {
+                let lines = if byte & (1 << bit) != 0 { 0x10 } else { 0x20 };
+                receiver.write(lines);
+                receiver.write(0x30);
+            }
+        }
+        receiver.write(0x20);
+        receiver.write(0x30);
+    }
+
+    fn packet(bytes: &[u8]) -> [u8; PACKET_SIZE] {
+        let mut packet = [0; PACKET_SIZE];
+        packet[..bytes.len()].copy_from_slice(bytes);
+        packet
+    }
+
+    #[test]
+    fn packets_are_assembled_from_joyp_pulses() {
+        let mut receiver = PacketReceiver::new();
+        let packet = packet(&[0x01, 0xa5, 0x5a, 0xff, 0x00, 0x81]);
+        send_packet(&mut receiver, &packet);
+
+        assert_eq!(receiver.commands, vec![packet.to_vec()]);
+    }
+
+    #[test]
+    fn commands_wait_for_all_their_packets() {
+        let mut receiver = PacketReceiver::new();
+        send_packet(&mut receiver, &packet(&[0x02, 0x12]));
+        assert!(receiver.commands.is_empty());
+
+        send_packet(&mut receiver, &packet(&[0x34]));
+        assert_eq!(receiver.commands.len(), 1);
+        assert_eq!(receiver.commands[0].len(), 2 * PACKET_SIZE);
+        assert_eq!(receiver.commands[0][PACKET_SIZE], 0x34);
+    }
+
+    #[test]
+    fn mlt_req_cycles_through_the_players_on_joyp() {
+        let mut receiver = PacketReceiver::new();
+        assert_eq!(receiver.joypad_id(), None);
+
+        send_packet(&mut receiver, &packet(&[MLT_REQ << 3 | 1, 0x01]));
+        assert_eq!(receiver.joypad_id(), Some(0x0f));
+
+        receiver.write(0x10);
+        assert_eq!(receiver.joypad_id(), None);
+        receiver.write(0x30);
+        assert_eq!(receiver.joypad_id(), Some(0x0e));
+
+        receiver.write(0x10);
+        receiver.write(0x30);
+        assert_eq!(receiver.joypad_id(), Some(0x0f));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod array;
+pub mod int;
+pub mod performance;
@@ -1,17 +1,19 @@
+#[allow(dead_code)]
 pub fn print_array_8(array: &[u8], width: usize) {
-    for i in 0..array.len() {
+    for (i, value) in array.iter().enumerate() {
         if i % width == 0 {
             println!();
         }
-        print!("{:02X} ", array[i]);
+        print!("{:02X} ", value);
     }
 }
 
+#[allow(dead_code)]
 pub fn print_array_16(array: &[u16], width: usize) {
-    for i in 0..array.len() {
+    for (i, value) in array.iter().enumerate() {
         if i % width == 0 {
             println!();
         }
-        print!("{:04X} ", array[i]);
+        print!("{:04X} ", value);
     }
 }
@@ -0,0 +1,27 @@
+use super::{BuiltinRom, Program};
+
+pub fn roms() -> Vec<BuiltinRom> {
+    vec![mbc1_ram()]
+}
+
+/// Bytes written to both ends of the MBC1 RAM area read back.
+fn mbc1_ram() -> BuiltinRom {
+    // MBC1+RAM+BATTERY
+    let mut program = Program::new().with_mbc_type(0x03);
+    #[rustfmt::skip]
+    program.emit(&[
+        0x3e, 0x0a,       // LD A,0x0a
+        0xea, 0x00, 0x00, // LD (0x0000),A: enables the RAM
+        0x3e, 0x12,       // LD A,0x12
+        0xea, 0x00, 0xa0, // LD (0xa000),A
+        0x3e, 0x34,       // LD A,0x34
+        0xea, 0xff, 0xbf, // LD (0xbfff),A
+        0xfa, 0x00, 0xa0, // LD A,(0xa000)
+    ]);
+    program.expect_a(0x12);
+    // LD A,(0xbfff)
+    program.emit(&[0xfa, 0xff, 0xbf]);
+    program.expect_a(0x34);
+
+    program.build("cartridge/mbc1_ram")
+}
@@ -0,0 +1,92 @@
+//! Test ROMs assembled at run time, following the mooneye pass/fail protocol.
+//!
+//! They cover behaviours checked by the public suites with ROMs small enough
+//! to live in the repository, so the harness always has something to run.
+
+mod cartridge;
+mod timer;
+
+const CODE_ADDRESS: u16 = 0x150;
+const MBC_TYPE_ADDRESS: usize = 0x147;
+
+pub struct BuiltinRom {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub fn all() -> Vec<BuiltinRom> {
+    let mut roms = cartridge::roms();
+    roms.extend(timer::roms());
+    roms
+}
+
+/// Straight-line test program checking values loaded in A.
+pub struct Program {
+    code: Vec<u8>,
+    mbc_type: u8,
+    /// Offsets in `code` of the `JP NZ` targets to patch with the fail handler
+    fail_jumps: Vec<usize>,
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program {
+            // DI
+            code: vec![0xf3],
+            mbc_type: 0x00,
+            fail_jumps: vec![],
+        }
+    }
+
+    pub fn with_mbc_type(mut self, mbc_type: u8) -> Program {
+        self.mbc_type = mbc_type;
+        self
+    }
+
+    pub fn emit(&mut self, bytes: &[u8]) -> &mut Program {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    /// Jumps to the fail handler unless A holds `value`.
+    pub fn expect_a(&mut self, value: u8) -> &mut Program {
+        // CP value; JP NZ,fail
+        self.emit(&[0xfe, value, 0xc2]);
+        self.fail_jumps.push(self.code.len());
+        self.emit(&[0x00, 0x00])
+    }
+
+    /// Appends the pass and fail handlers and returns a 32 KiB ROM image.
+    pub fn build(&mut self, name: &str) -> BuiltinRom {
+        // Fibonacci sequence in B, C, D, E, H and L, then the LD B,B breakpoint
+        #[rustfmt::skip]
+        self.emit(&[
+            0x06, 3, 0x0e, 5, 0x16, 8, 0x1e, 13, 0x26, 21, 0x2e, 34,
+            0x40,       // LD B,B
+            0x18, 0xfe, // JR -2
+        ]);
+
+        let fail = CODE_ADDRESS + self.code.len() as u16;
+        #[rustfmt::skip]
+        self.emit(&[
+            0x06, 0x42, 0x0e, 0x42, 0x16, 0x42, 0x1e, 0x42, 0x26, 0x42, 0x2e, 0x42,
+            0x40,       // LD B,B
+            0x18, 0xfe, // JR -2
+        ]);
+        for &offset in &self.fail_jumps {
+            self.code[offset..offset + 2].copy_from_slice(&fail.to_le_bytes());
+        }
+
+        let mut data = vec![0; 0x8000];
+        // NOP; JP 0x0150
+        data[0x100..0x104].copy_from_slice(&[0x00, 0xc3, 0x50, 0x01]);
+        data[MBC_TYPE_ADDRESS] = self.mbc_type;
+        let start = CODE_ADDRESS as usize;
+        data[start..start + self.code.len()].copy_from_slice(&self.code);
+
+        BuiltinRom {
+            name: format!("builtin/{}", name),
+            data,
+        }
+    }
+}
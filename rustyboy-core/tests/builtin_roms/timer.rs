@@ -0,0 +1,210 @@
+//! Timer ROMs in the spirit of mooneye's `acceptance/timer`, checking TIMA and
+//! DIV on exact M-cycles.
+//!
+//! Cycles are counted from the one writing DIV, a memory access happens on the
+//! last cycle of its instruction. With TAC=0x05 written on cycle 5, TIMA
+//! increments on cycles 8, 12, 16...
+
+use super::{BuiltinRom, Program};
+
+const DIV: u8 = 0x04;
+const TIMA: u8 = 0x05;
+const TMA: u8 = 0x06;
+const TAC: u8 = 0x07;
+
+pub fn roms() -> Vec<BuiltinRom> {
+    vec![
+        div_write(),
+        tim01(),
+        access_cycle(),
+        tima_reload(),
+        tima_write_reloading(),
+        tma_write_reloading(),
+        div_trigger(),
+        tac_write_trigger(),
+    ]
+}
+
+/// Emits timer accesses on given cycles, padding with NOPs.
+struct Timeline {
+    /// Cycle of the last access
+    cycle: u32,
+}
+
+impl Timeline {
+    /// Stops the timer, loads TIMA and TMA, writes DIV on cycle 0 then TAC on
+    /// cycle 5.
+    fn reset(program: &mut Program, tima: u8, tma: u8, tac: u8) -> Timeline {
+        #[rustfmt::skip]
+        program.emit(&[
+            0xaf,             // XOR A
+            0xe0, TAC,        // LDH (TAC),A
+            0x3e, tma,        // LD A,tma
+            0xe0, TMA,        // LDH (TMA),A
+            0x3e, tima,       // LD A,tima
+            0xe0, TIMA,       // LDH (TIMA),A
+            0xe0, DIV,        // LDH (DIV),A
+        ]);
+        let mut timeline = Timeline { cycle: 0 };
+        timeline.write(program, TAC, tac, 5);
+        timeline
+    }
+
+    fn pad(&mut self, program: &mut Program, length: u32, cycle: u32) {
+        let nops = cycle
+            .checked_sub(self.cycle + length)
+            .expect("access scheduled too early");
+        for _ in 0..nops {
+            program.emit(&[0x00]);
+        }
+        self.cycle = cycle;
+    }
+
+    /// Loads `register` in A on `cycle`.
+    fn read(&mut self, program: &mut Program, register: u8, cycle: u32) {
+        // LDH A,(register)
+        self.emit(program, &[0xf0, register], 3, cycle);
+    }
+
+    /// Emits `instruction` of `length` M-cycles so its last one is `cycle`.
+    fn emit(&mut self, program: &mut Program, instruction: &[u8], length: u32, cycle: u32) {
+        self.pad(program, length, cycle);
+        program.emit(instruction);
+    }
+
+    /// Writes `value` to `register` on `cycle`, from cycle 4 after the last
+    /// access for 0 and cycle 5 for other values.
+    fn write(&mut self, program: &mut Program, register: u8, value: u8, cycle: u32) {
+        if value == 0 {
+            self.pad(program, 4, cycle);
+            // XOR A
+            program.emit(&[0xaf]);
+        } else {
+            self.pad(program, 5, cycle);
+            // LD A,value
+            program.emit(&[0x3e, value]);
+        }
+        // LDH (register),A
+        program.emit(&[0xe0, register]);
+    }
+}
+
+/// Reads `register` on `cycle` after a reset and expects `value`.
+fn expect_read(program: &mut Program, setup: (u8, u8, u8), register: u8, cycle: u32, value: u8) {
+    let (tima, tma, tac) = setup;
+    Timeline::reset(program, tima, tma, tac).read(program, register, cycle);
+    program.expect_a(value);
+}
+
+/// DIV counts from 0 again after a write.
+fn div_write() -> BuiltinRom {
+    let mut program = Program::new();
+    expect_read(&mut program, (0, 0, 0x05), DIV, 63, 0x00);
+    expect_read(&mut program, (0, 0, 0x05), DIV, 64, 0x01);
+
+    program.build("timer/div_write")
+}
+
+/// TIMA increments every 4 M-cycles with TAC=0x05.
+fn tim01() -> BuiltinRom {
+    let mut program = Program::new();
+    expect_read(&mut program, (0, 0, 0x05), TIMA, 11, 0x01);
+    expect_read(&mut program, (0, 0, 0x05), TIMA, 12, 0x02);
+
+    program.build("timer/tim01")
+}
+
+/// Reads happen on the last M-cycle of instructions of any length.
+fn access_cycle() -> BuiltinRom {
+    let mut program = Program::new();
+    // LD C,TIMA
+    program.emit(&[0x0e, TIMA]);
+    let reads: [(&[u8], u32); 2] = [
+        // LDH A,(C)
+        (&[0xf2], 2),
+        // LD A,(0xff05)
+        (&[0xfa, TIMA, 0xff], 4),
+    ];
+
+    for (instruction, length) in reads {
+        for (cycle, value) in [(11, 0x01), (12, 0x02)] {
+            let mut timeline = Timeline::reset(&mut program, 0, 0, 0x05);
+            timeline.emit(&mut program, instruction, length, cycle);
+            program.expect_a(value);
+        }
+    }
+
+    program.build("timer/access_cycle")
+}
+
+/// TIMA reads 0 for an M-cycle after overflowing, then TMA.
+fn tima_reload() -> BuiltinRom {
+    let mut program = Program::new();
+    expect_read(&mut program, (0xfe, 0x42, 0x05), TIMA, 11, 0xff);
+    expect_read(&mut program, (0xfe, 0x42, 0x05), TIMA, 12, 0x00);
+    expect_read(&mut program, (0xfe, 0x42, 0x05), TIMA, 13, 0x42);
+
+    program.build("timer/tima_reload")
+}
+
+/// A TIMA write cancels a pending reload, one during the reload is ignored.
+fn tima_write_reloading() -> BuiltinRom {
+    let mut program = Program::new();
+
+    let mut timeline = Timeline::reset(&mut program, 0xfe, 0x42, 0x05);
+    timeline.write(&mut program, TIMA, 0x10, 12);
+    timeline.read(&mut program, TIMA, 15);
+    program.expect_a(0x10);
+
+    let mut timeline = Timeline::reset(&mut program, 0xfe, 0x42, 0x05);
+    timeline.write(&mut program, TIMA, 0x10, 13);
+    timeline.read(&mut program, TIMA, 16);
+    program.expect_a(0x43);
+
+    program.build("timer/tima_write_reloading")
+}
+
+/// A TMA write before or during the reload ends up in TIMA.
+fn tma_write_reloading() -> BuiltinRom {
+    let mut program = Program::new();
+
+    let mut timeline = Timeline::reset(&mut program, 0xfe, 0x42, 0x05);
+    timeline.write(&mut program, TMA, 0x77, 12);
+    timeline.read(&mut program, TIMA, 15);
+    program.expect_a(0x77);
+
+    let mut timeline = Timeline::reset(&mut program, 0xfe, 0x42, 0x05);
+    timeline.write(&mut program, TMA, 0x77, 13);
+    timeline.read(&mut program, TIMA, 16);
+    program.expect_a(0x78);
+
+    program.build("timer/tma_write_reloading")
+}
+
+/// Writing DIV increments TIMA when the selected counter bit is set.
+fn div_trigger() -> BuiltinRom {
+    let mut program = Program::new();
+
+    for (cycle, value) in [(9, 0x01), (10, 0x02)] {
+        let mut timeline = Timeline::reset(&mut program, 0, 0, 0x05);
+        timeline.write(&mut program, DIV, 0, cycle);
+        timeline.read(&mut program, TIMA, cycle + 3);
+        program.expect_a(value);
+    }
+
+    program.build("timer/div_trigger")
+}
+
+/// Disabling the timer increments TIMA when the selected counter bit is set.
+fn tac_write_trigger() -> BuiltinRom {
+    let mut program = Program::new();
+
+    for (cycle, value) in [(9, 0x01), (10, 0x02)] {
+        let mut timeline = Timeline::reset(&mut program, 0, 0, 0x05);
+        timeline.write(&mut program, TAC, 0, cycle);
+        timeline.read(&mut program, TIMA, cycle + 3);
+        program.expect_a(value);
+    }
+
+    program.build("timer/tac_write_trigger")
+}
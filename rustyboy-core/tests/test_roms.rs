@@ -0,0 +1,493 @@
+//! Runs the built-in test ROMs and the public suites found in `test_roms/`,
+//! then prints a summary.
+//!
+//! Public ROMs are not part of the repository, see `test_roms/README.md` to
+//! fetch them. The ROMs listed in `test_roms/passing.txt` are expected to pass:
+//! the run fails if any of them regresses or is missing. Set
+//! `RUSTYBOY_UPDATE_PASSING=1` to rewrite the list with the ROMs that currently
+//! pass.
+
+mod builtin_roms;
+
+use rustyboy_core::{Cartridge, Color, Hardware};
+use std::{
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::Mutex,
+    thread,
+};
+
+const TEST_ROMS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../test_roms");
+const PASSING_LIST: &str = "passing.txt";
+const PASSING_HEADER: &str = "# ROMs expected to pass, relative to the test_roms directory";
+const UPDATE_PASSING_VAR: &str = "RUSTYBOY_UPDATE_PASSING";
+
+// `LD B,B` is used by mooneye and dmg-acid2 as a software breakpoint
+const DEBUG_BREAKPOINT_OPCODE: u8 = 0x40;
+const BLARGG_SIGNATURE_ADDRESS: u16 = 0xa001;
+const BLARGG_SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const BLARGG_STATUS_ADDRESS: u16 = 0xa000;
+const BLARGG_TEXT_ADDRESS: u16 = 0xa004;
+const BLARGG_RUNNING: u8 = 0x80;
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suite {
+    Builtin,
+    Blargg,
+    Mooneye,
+    DmgAcid2,
+}
+
+impl Suite {
+    const ALL: [Suite; 4] = [
+        Suite::Builtin,
+        Suite::Blargg,
+        Suite::Mooneye,
+        Suite::DmgAcid2,
+    ];
+
+    fn directory(&self) -> &'static str {
+        match self {
+            Suite::Builtin => "builtin",
+            Suite::Blargg => "blargg",
+            Suite::Mooneye => "mooneye",
+            Suite::DmgAcid2 => "dmg-acid2",
+        }
+    }
+
+    fn max_frames(&self) -> u32 {
+        match self {
+            Suite::Builtin => 60,
+            Suite::Blargg => 60 * 120,
+            Suite::Mooneye => 60 * 30,
+            Suite::DmgAcid2 => 60 * 5,
+        }
+    }
+}
+
+enum Outcome {
+    Passed,
+    Failed(String),
+    Timeout,
+    Crashed(String),
+}
+
+impl Outcome {
+    fn is_pass(&self) -> bool {
+        matches!(self, Outcome::Passed)
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "pass"),
+            Outcome::Failed(reason) => write!(f, "FAIL ({})", reason),
+            Outcome::Timeout => write!(f, "FAIL (timeout)"),
+            Outcome::Crashed(reason) => write!(f, "FAIL (crash: {})", reason),
+        }
+    }
+}
+
+enum RomSource {
+    File(PathBuf),
+    Builtin(Vec<u8>),
+}
+
+struct TestRom {
+    suite: Suite,
+    source: RomSource,
+    name: String,
+}
+
+fn main() {
+    let root = Path::new(TEST_ROMS_DIR);
+    let mut roms = builtin_roms::all()
+        .into_iter()
+        .map(|rom| TestRom {
+            suite: Suite::Builtin,
+            source: RomSource::Builtin(rom.data),
+            name: rom.name,
+        })
+        .collect::<Vec<_>>();
+    roms.extend(find_roms(root));
+
+    // Emulator panics are reported as crashes of the ROM that caused them
+    panic::set_hook(Box::new(|_| {}));
+    let outcomes = run_parallel(&roms);
+    let _ = panic::take_hook();
+
+    print_summary(&roms, &outcomes);
+
+    let passing_path = root.join(PASSING_LIST);
+    let expected = fs::read_to_string(&passing_path).unwrap_or_default();
+    let expected: Vec<_> = expected
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let missing: Vec<_> = expected
+        .iter()
+        .filter(|name| !roms.iter().any(|rom| rom.name == **name))
+        .collect();
+
+    if env::var_os(UPDATE_PASSING_VAR).is_some() {
+        // ROMs that weren't fetched keep their entry
+        let mut passing: Vec<_> = roms
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, outcome)| outcome.is_pass())
+            .map(|(rom, _)| rom.name.as_str())
+            .chain(missing.iter().map(|name| **name))
+            .collect();
+        passing.sort();
+        passing.insert(0, PASSING_HEADER);
+        fs::write(&passing_path, passing.join("\n") + "\n").unwrap();
+        println!("Updated {}", passing_path.display());
+        return;
+    }
+
+    let regressions: Vec<_> = expected
+        .iter()
+        .filter(|name| {
+            roms.iter()
+                .zip(&outcomes)
+                .any(|(rom, outcome)| rom.name == **name && !outcome.is_pass())
+        })
+        .collect();
+
+    if !missing.is_empty() {
+        println!("\nMissing, see test_roms/README.md to fetch them:");
+        for name in &missing {
+            println!("  {}", name);
+        }
+    }
+    if !regressions.is_empty() {
+        println!("\nRegressions:");
+        for name in &regressions {
+            println!("  {}", name);
+        }
+    }
+    if !missing.is_empty() || !regressions.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn find_roms(root: &Path) -> Vec<TestRom> {
+    let mut roms = vec![];
+    for suite in Suite::ALL {
+        if suite == Suite::Builtin {
+            continue;
+        }
+
+        let mut paths = vec![];
+        collect_roms(&root.join(suite.directory()), &mut paths);
+        paths.sort();
+
+        roms.extend(paths.into_iter().map(|path| {
+            TestRom {
+                suite,
+                name: path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                source: RomSource::File(path),
+            }
+        }));
+    }
+    roms
+}
+
+fn collect_roms(directory: &Path, paths: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_roms(&path, paths);
+        } else if path.extension().is_some_and(|extension| extension == "gb") {
+            paths.push(path);
+        }
+    }
+}
+
+fn run_parallel(roms: &[TestRom]) -> Vec<Outcome> {
+    let next = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Outcome>>> = Mutex::new(roms.iter().map(|_| None).collect());
+    let workers = thread::available_parallelism().map_or(1, |count| count.get());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let rom = match roms.get(index) {
+                    Some(rom) => rom,
+                    None => break,
+                };
+
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| run_rom(rom)))
+                    .unwrap_or_else(|payload| Outcome::Crashed(panic_message(payload)));
+                outcomes.lock().unwrap()[index] = Some(outcome);
+            });
+        }
+    });
+
+    outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|outcome| outcome.unwrap())
+        .collect()
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn run_rom(rom: &TestRom) -> Outcome {
+    let cartridge = match &rom.source {
+        RomSource::File(path) => Cartridge::from_path(path.to_string_lossy().to_string()),
+        RomSource::Builtin(data) => Cartridge::from_bytes(data.clone()),
+    };
+    let cartridge = match cartridge {
+        Ok(cartridge) => cartridge,
+        Err(error) => return Outcome::Crashed(error.to_string()),
+    };
+    let mut hardware = Hardware::new(cartridge);
+
+    match rom.suite {
+        Suite::Blargg => run_blargg(&mut hardware, rom.suite.max_frames()),
+        Suite::Builtin | Suite::Mooneye => run_mooneye(&mut hardware, rom.suite.max_frames()),
+        Suite::DmgAcid2 => run_dmg_acid2(&mut hardware, rom),
+    }
+}
+
+/// Blargg's ROMs print their result on the serial port, newer ones also store
+/// it in cartridge RAM after a signature.
+fn run_blargg(hardware: &mut Hardware, max_frames: u32) -> Outcome {
+    let mut frames = 0;
+    while frames < max_frames {
+        if !hardware.step() {
+            continue;
+        }
+        frames += 1;
+
+        let serial = String::from_utf8_lossy(hardware.serial_output());
+        if serial.contains("Passed") {
+            return Outcome::Passed;
+        }
+        if serial.contains("Failed") {
+            return Outcome::Failed(last_line(&serial));
+        }
+
+        let signature = [0, 1, 2].map(|i| hardware.read_memory(BLARGG_SIGNATURE_ADDRESS + i));
+        let status = hardware.read_memory(BLARGG_STATUS_ADDRESS);
+        if signature == BLARGG_SIGNATURE && status != BLARGG_RUNNING {
+            return match status {
+                0 => Outcome::Passed,
+                code => Outcome::Failed(format!("code {:02X}: {}", code, blargg_text(hardware))),
+            };
+        }
+    }
+
+    Outcome::Timeout
+}
+
+fn blargg_text(hardware: &Hardware) -> String {
+    let text: Vec<u8> = (BLARGG_TEXT_ADDRESS..0xc000)
+        .map(|address| hardware.read_memory(address))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    last_line(&String::from_utf8_lossy(&text))
+}
+
+fn last_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Runs until the next `LD B,B` breakpoint, returns `false` on timeout.
+fn run_to_breakpoint(hardware: &mut Hardware, max_frames: u32) -> bool {
+    let mut frames = 0;
+    while frames < max_frames {
+        if hardware.read_memory(hardware.program_counter()) == DEBUG_BREAKPOINT_OPCODE {
+            return true;
+        }
+        if hardware.step() {
+            frames += 1;
+        }
+    }
+
+    false
+}
+
+/// Mooneye's ROMs load the Fibonacci sequence in B, C, D, E, H and L on
+/// success before hitting the breakpoint.
+fn run_mooneye(hardware: &mut Hardware, max_frames: u32) -> Outcome {
+    if !run_to_breakpoint(hardware, max_frames) {
+        return Outcome::Timeout;
+    }
+
+    let registers = hardware.registers();
+    let values = [
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+    ];
+    if values == MOONEYE_PASS {
+        Outcome::Passed
+    } else {
+        Outcome::Failed(format!("registers {:02X?}", values))
+    }
+}
+
+/// dmg-acid2 draws its test image and hits the breakpoint; the next frame is
+/// compared with the reference image stored next to the ROM.
+fn run_dmg_acid2(hardware: &mut Hardware, rom: &TestRom) -> Outcome {
+    let path = match &rom.source {
+        RomSource::File(path) => path,
+        RomSource::Builtin(_) => return Outcome::Crashed("no reference image".to_string()),
+    };
+    let reference = match load_reference(path) {
+        Ok(reference) => reference,
+        Err(error) => return Outcome::Crashed(error),
+    };
+
+    if !run_to_breakpoint(hardware, rom.suite.max_frames()) {
+        return Outcome::Timeout;
+    }
+
+    let frame = hardware.run();
+    let mismatches = frame
+        .iter()
+        .zip(reference.iter())
+        .filter(|(&color, &expected)| shade(color) != expected)
+        .count();
+
+    if mismatches == 0 {
+        Outcome::Passed
+    } else {
+        Outcome::Failed(format!("{} pixels differ from the reference", mismatches))
+    }
+}
+
+fn shade(color: Color) -> u8 {
+    match color {
+        Color::Transparent | Color::White => 0,
+        Color::LightGray => 1,
+        Color::DarkGray => 2,
+        Color::Black => 3,
+        Color::Sprite(_, shade) => shade,
+        // References are DMG screenshots, CGB colors are bucketed by brightness
+        Color::Rgb(_) => 3 - color.luma() / 64,
+    }
+}
+
+/// Loads `<rom>.png` or `reference-dmg.png` as a list of shades.
+fn load_reference(rom_path: &Path) -> Result<Vec<u8>, String> {
+    let candidates = [
+        rom_path.with_extension("png"),
+        rom_path.with_file_name("reference-dmg.png"),
+    ];
+    let path = candidates
+        .iter()
+        .find(|path| path.exists())
+        .ok_or_else(|| "missing reference image".to_string())?;
+
+    let file = fs::File::open(path).map_err(|error| error.to_string())?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|error| error.to_string())?;
+
+    if (info.width, info.height) != (160, 144) {
+        return Err(format!("reference image is {}x{}", info.width, info.height));
+    }
+
+    let channels = info.color_type.samples();
+    Ok(data[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| {
+            let luma = match channels {
+                1 | 2 => pixel[0] as u32,
+                _ => (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000,
+            };
+            match luma {
+                0xc0.. => 0,
+                0x80..=0xbf => 1,
+                0x40..=0x7f => 2,
+                _ => 3,
+            }
+        })
+        .collect())
+}
+
+fn print_summary(roms: &[TestRom], outcomes: &[Outcome]) {
+    let name_width = roms.iter().map(|rom| rom.name.len()).max().unwrap_or(0);
+
+    for (rom, outcome) in roms.iter().zip(outcomes) {
+        println!("{:<width$}  {}", rom.name, outcome, width = name_width);
+    }
+
+    println!(
+        "\n{:<12} {:>7} {:>7} {:>7}",
+        "Suite", "Passed", "Total", "Status"
+    );
+    for suite in Suite::ALL {
+        let results: Vec<_> = roms
+            .iter()
+            .zip(outcomes)
+            .filter(|(rom, _)| rom.suite == suite)
+            .collect();
+        if results.is_empty() {
+            println!(
+                "{:<12} {:>7} {:>7} {:>7}",
+                suite.directory(),
+                "-",
+                "-",
+                "missing"
+            );
+            continue;
+        }
+
+        let passed = results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_pass())
+            .count();
+        let status = if passed == results.len() {
+            "pass"
+        } else {
+            "FAIL"
+        };
+        println!(
+            "{:<12} {:>7} {:>7} {:>7}",
+            suite.directory(),
+            passed,
+            results.len(),
+            status
+        );
+    }
+}
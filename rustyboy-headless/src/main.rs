@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Result};
+use rustyboy_core::{save_png, save_sgb_png, Cartridge, Color, DisplayPalette, Hardware, TcpLink};
+use std::{env, process};
+
+const EXIT_CONDITION_MET: i32 = 0;
+const EXIT_CONDITION_NOT_MET: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+const DEFAULT_FRAMES: u32 = 60 * 60;
+
+const USAGE: &str = "Usage: rustyboy-headless <rom> [options]
+
+Options:
+  --frames <n>               Maximum number of frames to run (default 3600)
+  --until-serial <text>      Stop when the serial output contains <text>
+  --until-pc <address>       Stop when the program counter reaches <address>
+  --until-memory <addr=val>  Stop when the byte at <addr> equals <val>
+  --output <path>            Write the last frame to <path> (PNG)
+  --palette <name|path>      Colors of the output: grayscale, green, pocket,
+                             light or a palette config file
+  --color-correction         Show CGB colors as they look on the CGB LCD
+  --sgb-border               Write the 256x224 SGB screen, border included,
+                             for SGB games
+  --serial-echo              Print the serial output as it is sent
+  --link-listen <address>    Wait for another emulator to plug the link cable
+  --link-connect <address>   Plug the link cable into the emulator listening
+                             on <address>
+  --profile                  Print the profiler reports at the end of the run
+  --symbols <path>           Symbol file used by the profiler reports
+  --no-access-restrictions   Let the CPU access VRAM and OAM in every PPU mode
+
+Exit status is 0 when a stop condition is met (or all frames ran when no
+condition is given), 1 when the frames ran out first and 2 on errors.";
+
+enum StopCondition {
+    Serial(String),
+    ProgramCounter(u16),
+    Memory(u16, u8),
+}
+
+/// Role of this emulator on the link cable
+enum LinkCable {
+    Listen(String),
+    Connect(String),
+}
+
+impl StopCondition {
+    fn is_met(&self, hardware: &Hardware) -> bool {
+        match self {
+            StopCondition::Serial(text) => {
+                String::from_utf8_lossy(hardware.serial_output()).contains(text.as_str())
+            }
+            StopCondition::ProgramCounter(address) => hardware.program_counter() == *address,
+            StopCondition::Memory(address, value) => hardware.read_memory(*address) == *value,
+        }
+    }
+}
+
+struct Options {
+    rom_path: String,
+    frames: u32,
+    conditions: Vec<StopCondition>,
+    output_path: Option<String>,
+    palette: DisplayPalette,
+    sgb_border: bool,
+    serial_echo: bool,
+    link: Option<LinkCable>,
+    profile: bool,
+    symbols_path: Option<String>,
+    access_restrictions: bool,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Options> {
+        let mut options = Options {
+            rom_path: args.next().ok_or_else(|| anyhow!("No ROM path provided"))?,
+            frames: DEFAULT_FRAMES,
+            conditions: vec![],
+            output_path: None,
+            palette: DisplayPalette::default(),
+            sgb_border: false,
+            serial_echo: false,
+            link: None,
+            profile: false,
+            symbols_path: None,
+            access_restrictions: true,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--frames" => options.frames = value()?.parse()?,
+                "--until-serial" => options.conditions.push(StopCondition::Serial(value()?)),
+                "--until-pc" => options
+                    .conditions
+                    .push(StopCondition::ProgramCounter(parse_number(&value()?)?)),
+                "--until-memory" => {
+                    let value = value()?;
+                    let (address, expected) = value
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("Expected <address>=<value>, got {}", value))?;
+                    let expected = u8::try_from(parse_number(expected)?)?;
+                    options
+                        .conditions
+                        .push(StopCondition::Memory(parse_number(address)?, expected));
+                }
+                "--output" => options.output_path = Some(value()?),
+                "--palette" => {
+                    let color_correction = options.palette.color_correction;
+                    options.palette = DisplayPalette::find(&value()?)?;
+                    options.palette.color_correction |= color_correction;
+                }
+                "--color-correction" => options.palette.color_correction = true,
+                "--sgb-border" => options.sgb_border = true,
+                "--serial-echo" => options.serial_echo = true,
+                "--link-listen" => options.link = Some(LinkCable::Listen(value()?)),
+                "--link-connect" => options.link = Some(LinkCable::Connect(value()?)),
+                "--profile" => options.profile = true,
+                "--symbols" => options.symbols_path = Some(value()?),
+                "--no-access-restrictions" => options.access_restrictions = false,
+                _ => return Err(anyhow!("Unknown option {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Parses a decimal number, or a hexadecimal one when prefixed by `0x` or `$`.
+fn parse_number(value: &str) -> Result<u16> {
+    let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix('$'));
+    let number = match hex {
+        Some(hex) => u16::from_str_radix(hex, 16)?,
+        None => value.parse()?,
+    };
+
+    Ok(number)
+}
+
+fn main() {
+    let status = match run() {
+        Ok(status) => status,
+        Err(error) => {
+            eprintln!("Error: {}\n\n{}", error, USAGE);
+            EXIT_ERROR
+        }
+    };
+
+    process::exit(status);
+}
+
+fn run() -> Result<i32> {
+    let options = Options::from_args(env::args().skip(1))?;
+
+    let cartridge = Cartridge::from_path(options.rom_path.clone())?;
+    println!("Running {}", cartridge.header.title);
+
+    let mut builder = Hardware::builder(cartridge)
+        .profiling(options.profile)
+        .serial_echo(options.serial_echo)
+        .access_restrictions(options.access_restrictions);
+    if let Some(symbols_path) = &options.symbols_path {
+        builder = builder.symbols(symbols_path);
+    }
+    let mut hardware = builder.build()?;
+    match &options.link {
+        Some(LinkCable::Listen(address)) => {
+            println!("Waiting for the link cable on {}", address);
+            hardware.connect_serial(Box::new(TcpLink::listen(address)?));
+        }
+        Some(LinkCable::Connect(address)) => {
+            hardware.connect_serial(Box::new(TcpLink::connect(address)?));
+        }
+        None => {}
+    }
+
+    let mut frames = 0;
+    let mut last_frame = [Color::White; 160 * 144];
+    let condition_met = loop {
+        if hardware.step() {
+            last_frame = *hardware.frame();
+            frames += 1;
+        }
+
+        if options
+            .conditions
+            .iter()
+            .any(|condition| condition.is_met(&hardware))
+        {
+            break true;
+        }
+
+        if frames >= options.frames {
+            break options.conditions.is_empty();
+        }
+    };
+
+    // With the echo on, the serial output was already printed
+    let serial_output = hardware.serial_output();
+    if !serial_output.is_empty() && !options.serial_echo {
+        println!("Serial output:\n{}", String::from_utf8_lossy(serial_output));
+    }
+
+    if let Some(profiler) = hardware.profiler() {
+        println!("{}", profiler.flat_report());
+        println!("{}", profiler.tree_report());
+    }
+
+    if let Some(path) = &options.output_path {
+        match hardware.sgb_screen() {
+            Some(screen) if options.sgb_border => save_sgb_png(path, screen, &options.palette)?,
+            _ => save_png(path, &last_frame, &options.palette)?,
+        }
+    }
+
+    if condition_met {
+        println!("Stopped after {} frames", frames);
+        Ok(EXIT_CONDITION_MET)
+    } else {
+        println!("No stop condition met after {} frames", frames);
+        Ok(EXIT_CONDITION_NOT_MET)
+    }
+}
@@ -1,18 +1,15 @@
-use crate::{joypad::JoypadKey, utils::time::TimeFrame};
+use crate::utils::time::TimeFrame;
 use anyhow::Result;
-use cartridge::Cartridge;
 use glium::glutin::event::KeyboardInput;
-use std::{env, time::Duration};
+use rustyboy_core::{
+    save_png, save_sgb_png, Cartridge, Color, DisplayPalette, Hardware, JoypadKey, TcpLink,
+    SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH,
+};
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::hardware::Hardware;
-
-mod cartridge;
-mod cpu;
-mod hardware;
-mod joypad;
-mod lcd;
-mod memory;
-mod ppu;
 mod renderer;
 mod utils;
 
@@ -21,20 +18,50 @@ fn main() -> Result<()> {
     let cartridge = Cartridge::from_path(rom_path)?;
     println!("Running {}", cartridge.header.title);
 
-    let hardware = Hardware::new(cartridge);
+    let mut builder =
+        Hardware::builder(cartridge).profiling(env::args().any(|arg| arg == "--profile"));
+    if let Some(symbols_path) = env::args().skip_while(|arg| arg != "--symbols").nth(1) {
+        builder = builder.symbols(&symbols_path);
+    }
+
+    let mut hardware = builder.build()?;
+    if let Some(address) = env::args().skip_while(|arg| arg != "--link-listen").nth(1) {
+        println!("Waiting for the link cable on {}", address);
+        hardware.connect_serial(Box::new(TcpLink::listen(&address)?));
+    } else if let Some(address) = env::args().skip_while(|arg| arg != "--link-connect").nth(1) {
+        hardware.connect_serial(Box::new(TcpLink::connect(&address)?));
+    }
+
+    let mut palette = match env::args().skip_while(|arg| arg != "--palette").nth(1) {
+        Some(value) => DisplayPalette::find(&value)?,
+        None => DisplayPalette::default(),
+    };
+    if env::args().any(|arg| arg == "--color-correction") {
+        palette.color_correction = true;
+    }
 
-    create_window(hardware);
+    create_window(hardware, palette);
 
     Ok(())
 }
 
-fn create_window(mut hardware: Hardware) {
+fn create_window(mut hardware: Hardware, palette: DisplayPalette) {
     use glium::glutin;
 
     let event_loop = glutin::event_loop::EventLoop::new();
 
+    // SGB games are shown inside their border
+    let size = if hardware.sgb_screen().is_some() {
+        (SGB_SCREEN_WIDTH as u32, SGB_SCREEN_HEIGHT as u32)
+    } else {
+        (160, 144)
+    };
+
     let wb = glutin::window::WindowBuilder::new()
-        .with_inner_size(glutin::dpi::LogicalSize::new(320.0, 288.0))
+        .with_inner_size(glutin::dpi::LogicalSize::new(
+            size.0 as f64 * 2.0,
+            size.1 as f64 * 2.0,
+        ))
         .with_title("Rustyboy");
 
     let cb = glutin::ContextBuilder::new();
@@ -42,6 +69,7 @@ fn create_window(mut hardware: Hardware) {
     let display = glium::Display::new(wb, cb, &event_loop).unwrap();
 
     let mut time_frame = TimeFrame::new(Duration::from_secs(1) / 60);
+    let mut last_frame = [Color::White; 160 * 144];
 
     event_loop.run(move |ev, _, control_flow| {
         *control_flow = glutin::event_loop::ControlFlow::Poll;
@@ -49,18 +77,22 @@ fn create_window(mut hardware: Hardware) {
         match ev {
             glutin::event::Event::WindowEvent { event, .. } => match event {
                 glutin::event::WindowEvent::CloseRequested => {
+                    print_profile_report(&hardware);
                     *control_flow = glutin::event_loop::ControlFlow::Exit;
                 }
                 glutin::event::WindowEvent::KeyboardInput { input, .. } => {
-                    handle_key_event(input, &mut hardware);
+                    handle_key_event(input, &mut hardware, &last_frame, &palette);
                 }
                 _ => (),
             },
             glutin::event::Event::NewEvents(_) => {
                 time_frame.update();
-                let buffer = hardware.run();
+                last_frame = hardware.run();
 
-                renderer::render(&display, buffer);
+                match hardware.sgb_screen() {
+                    Some(screen) => renderer::render(&display, screen, size, &palette),
+                    None => renderer::render(&display, &last_frame, size, &palette),
+                }
             }
             glutin::event::Event::RedrawEventsCleared => {
                 time_frame.wait();
@@ -70,46 +102,78 @@ fn create_window(mut hardware: Hardware) {
     });
 }
 
-fn handle_key_event(input: KeyboardInput, hardware: &mut Hardware) {
+fn handle_key_event(
+    input: KeyboardInput,
+    hardware: &mut Hardware,
+    last_frame: &[Color; 160 * 144],
+    palette: &DisplayPalette,
+) {
     use glium::glutin::{self, event::VirtualKeyCode};
 
-    match input {
-        KeyboardInput {
-            virtual_keycode: Some(key),
-            state,
-            ..
-        } => {
-            let joypad_key = match key {
-                VirtualKeyCode::A => Some(JoypadKey::A),
-                VirtualKeyCode::S => Some(JoypadKey::B),
-                VirtualKeyCode::Back => Some(JoypadKey::Select),
-                VirtualKeyCode::Return => Some(JoypadKey::Start),
-                VirtualKeyCode::Up => Some(JoypadKey::Up),
-                VirtualKeyCode::Down => Some(JoypadKey::Down),
-                VirtualKeyCode::Left => Some(JoypadKey::Left),
-                VirtualKeyCode::Right => Some(JoypadKey::Right),
-                _ => None,
-            };
-
-            if let Some(key) = joypad_key {
-                match state {
-                    glutin::event::ElementState::Pressed => {
-                        hardware.button_pressed(key);
-                    }
-                    glutin::event::ElementState::Released => {
-                        hardware.button_released(key);
-                    }
+    if let KeyboardInput {
+        virtual_keycode: Some(key),
+        state,
+        ..
+    } = input
+    {
+        let joypad_key = match key {
+            VirtualKeyCode::A => Some(JoypadKey::A),
+            VirtualKeyCode::S => Some(JoypadKey::B),
+            VirtualKeyCode::Back => Some(JoypadKey::Select),
+            VirtualKeyCode::Return => Some(JoypadKey::Start),
+            VirtualKeyCode::Up => Some(JoypadKey::Up),
+            VirtualKeyCode::Down => Some(JoypadKey::Down),
+            VirtualKeyCode::Left => Some(JoypadKey::Left),
+            VirtualKeyCode::Right => Some(JoypadKey::Right),
+            _ => None,
+        };
+
+        if let Some(key) = joypad_key {
+            match state {
+                glutin::event::ElementState::Pressed => {
+                    hardware.button_pressed(key);
+                }
+                glutin::event::ElementState::Released => {
+                    hardware.button_released(key);
                 }
             }
+        }
 
-            if let VirtualKeyCode::Escape = key {
-                std::process::exit(0);
-            }
+        if let VirtualKeyCode::Escape = key {
+            print_profile_report(hardware);
+            std::process::exit(0);
+        }
 
-            if let VirtualKeyCode::T = key {
-                hardware.enable_tracing();
-            }
+        if let VirtualKeyCode::T = key {
+            hardware.enable_tracing();
         }
-        _ => (),
+
+        if let (VirtualKeyCode::P, glutin::event::ElementState::Pressed) = (key, state) {
+            save_screenshot(hardware, last_frame, palette);
+        }
+    }
+}
+
+fn save_screenshot(hardware: &Hardware, frame: &[Color; 160 * 144], palette: &DisplayPalette) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let path = format!("screenshot-{}.png", timestamp);
+
+    let result = match hardware.sgb_screen() {
+        Some(screen) => save_sgb_png(&path, screen, palette),
+        None => save_png(&path, frame, palette),
+    };
+    match result {
+        Ok(()) => println!("Screenshot saved to {}", path),
+        Err(error) => println!("Failed to save screenshot: {}", error),
+    }
+}
+
+fn print_profile_report(hardware: &Hardware) {
+    if let Some(profiler) = hardware.profiler() {
+        println!("{}", profiler.flat_report());
+        println!("{}", profiler.tree_report());
     }
 }
@@ -1,17 +1,15 @@
 use glium::{texture::RawImage2d, uniforms::MagnifySamplerFilter, Display, Surface};
 
-use crate::ppu::palette::Color;
+use rustyboy_core::{Color, DisplayPalette};
 
-pub fn render(display: &Display, buffer: [Color; 160 * 144]) {
+/// Draws a frame of `size` pixels, stretched to the whole window.
+pub fn render(display: &Display, buffer: &[Color], size: (u32, u32), palette: &DisplayPalette) {
     let target = display.draw();
     // target.clear_color(1.0, 0.0, 0.0, 1.0);
 
-    let color_buffer: Vec<u8> = buffer
-        .iter()
-        .flat_map(|color| color_to_bytes(color))
-        .collect();
+    let color_buffer = palette.frame_to_rgb(buffer);
 
-    let image = RawImage2d::from_raw_rgb_reversed(&color_buffer, (160, 144));
+    let image = RawImage2d::from_raw_rgb_reversed(&color_buffer, size);
     let texture = glium::texture::Texture2d::new(display, image).unwrap();
 
     texture
@@ -20,13 +18,3 @@ pub fn render(display: &Display, buffer: [Color; 160 * 144]) {
 
     target.finish().unwrap();
 }
-
-fn color_to_bytes(color: &Color) -> [u8; 3] {
-    match color {
-        Color::Transparent => [0x00, 0x00, 0x00],
-        Color::White => [0xff, 0xff, 0xff],
-        Color::LightGray => [0xcc, 0xcc, 0xcc],
-        Color::DarkGray => [0x77, 0x77, 0x77],
-        Color::Black => [0x00, 0x00, 0x00],
-    }
-}
@@ -1,4 +1 @@
-pub mod array;
-pub mod int;
-pub mod performance;
 pub mod time;
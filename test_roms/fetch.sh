@@ -0,0 +1,33 @@
+#!/bin/sh
+# Fetches the public test ROM suites into this directory.
+#
+# Blargg's ROMs are committed prebuilt upstream. Mooneye and dmg-acid2 are
+# built from source, which needs git, make and rgbds (https://rgbds.gbdev.io).
+set -eu
+
+cd "$(dirname "$0")"
+sources=.sources
+mkdir -p "$sources"
+
+fetch() {
+    if [ ! -d "$sources/$1" ]; then
+        git clone --depth 1 "$2" "$sources/$1"
+    fi
+}
+
+fetch gb-test-roms https://github.com/retrio/gb-test-roms
+fetch mooneye-test-suite https://github.com/Gekkio/mooneye-test-suite
+fetch dmg-acid2 https://github.com/mattcurrie/dmg-acid2
+
+rm -rf blargg mooneye dmg-acid2
+
+mkdir blargg
+(cd "$sources/gb-test-roms" && find . -name '*.gb' -exec cp --parents {} ../../blargg \;)
+
+make -C "$sources/mooneye-test-suite"
+mkdir mooneye
+(cd "$sources/mooneye-test-suite/build" && find . -name '*.gb' -exec cp --parents {} ../../../mooneye \;)
+
+make -C "$sources/dmg-acid2"
+mkdir dmg-acid2
+cp "$sources/dmg-acid2/build/dmg-acid2.gb" "$sources/dmg-acid2/img/reference-dmg.png" dmg-acid2